@@ -0,0 +1,64 @@
+// times the same steps as the numbers in benchmarking.md, reading a million line csv, parsing it
+// into an Excel and writing it back out. the input is built in memory from the first 16 columns of
+// test_data/medium.csv (about 80 MB) so no large file has to be checked in. run with `cargo bench`.
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use excel_takehome::csv::{Dialect, Parser};
+use excel_takehome::excel::Excel;
+
+const LINES: usize = 1_000_000;
+const RUNS: usize = 3;
+const COLUMNS: usize = 16;
+
+fn large_csv() -> Vec<u8> {
+    let medium = std::fs::read_to_string("test_data/medium.csv").unwrap();
+    // none of the first columns have a quoted comma in them, so splitting on commas is fine.
+    let mut lines = medium
+        .lines()
+        .map(|line| line.split(',').take(COLUMNS).collect::<Vec<_>>().join(","));
+    let header = lines.next().unwrap();
+    let body: Vec<String> = lines.collect();
+
+    let mut src = Vec::new();
+    src.extend_from_slice(header.as_bytes());
+    src.push(b'\n');
+    for line in body.iter().cycle().take(LINES) {
+        src.extend_from_slice(line.as_bytes());
+        src.push(b'\n');
+    }
+    src
+}
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        f();
+        best = best.min(start.elapsed());
+    }
+    println!("{:<24} {:>8.3}s (best of {})", name, best.as_secs_f64(), RUNS);
+}
+
+fn main() {
+    let src = large_csv();
+    let out = std::env::temp_dir().join("excel_takehome_bench.csv");
+    let out = out.to_str().unwrap();
+    println!("{} lines, {} MB", LINES, src.len() / 1_000_000);
+
+    bench("tokenize", || {
+        black_box(Parser::new(&src, Dialect::default()).unwrap().parse());
+    });
+    bench("parse into Excel", || {
+        black_box(Excel::from_bytes(&src, Dialect::default()).unwrap());
+    });
+    bench("parse and write", || {
+        let excel = Excel::from_bytes(&src, Dialect::default()).unwrap();
+        excel.to_file(out).unwrap();
+    });
+
+    std::fs::remove_file(out).ok();
+
+    let medium = std::fs::read_to_string("test_data/medium.csv").unwrap();
+    println!("\ntest_data/medium.csv\n{}", Excel::new(medium).memory_report());
+    println!("\n{} lines\n{}", LINES, Excel::from_bytes(&src, Dialect::default()).unwrap().memory_report());
+}
@@ -0,0 +1,438 @@
+use std::fmt;
+use crate::csv::{LineTerminator, Quoting};
+use crate::excel::{CellAddress, OutputMode};
+
+pub const USAGE: &str = "\
+Usage: excel_takehome <command> [options] [file]
+
+Reads a csv or .xlsx file, or csv from stdin when the file is - or left out.
+
+Commands:
+  convert    rewrite the file in another dialect
+  eval       work out every formula and write the values
+  validate   check the file parses cleanly, exits with 1 if it doesn't
+  stats      row and column counts and each column's type
+  get        print the value of a cell or range, like get data.csv B2 (also query)
+  repl       try out formulas and change cells interactively
+  view       show the grid as a table, a page at a time in a terminal
+
+Run excel_takehome <command> --help for a command's options.";
+
+const CONVERT_HELP: &str = "\
+Usage: excel_takehome convert [options] [file]
+
+Writes the file back out, by default in the dialect it was read in.
+
+Options:
+  -o, --output <file>          where to write, stdout when - or left out
+  -d, --delimiter <char>       delimiter to write with, \"tab\" for tabs
+      --quoting <policy>       minimal, all, non-numeric or never
+      --line-terminator <end>  lf, crlf or cr
+      --mode <mode>            formulas (default), values or both
+      --schema <file>          read each column as the type the schema gives it
+      --sheet <name>           the sheet to read from an .xlsx file, the first by default
+      --header                 the first row holds column names";
+
+const EVAL_HELP: &str = "\
+Usage: excel_takehome eval [options] [file]
+
+Works out every formula and writes the grid with their values in place.
+
+Options:
+  -o, --output <file>          where to write, stdout when - or left out
+  -d, --delimiter <char>       delimiter to write with, \"tab\" for tabs
+      --quoting <policy>       minimal, all, non-numeric or never
+      --line-terminator <end>  lf, crlf or cr
+      --mode <mode>            values (default), or both to keep the formulas alongside
+      --sheet <name>           the sheet to read from an .xlsx file, the first by default
+      --header                 the first row holds column names";
+
+const VALIDATE_HELP: &str = "\
+Usage: excel_takehome validate [options] [file]
+
+Lists every row that doesn't parse and, with a schema, every cell that doesn't fit it.
+Exits with 0 when the file is fine, 1 when it isn't and 2 when it couldn't be read.
+
+Options:
+      --schema <file>          check each column against the type the schema gives it
+      --sheet <name>           the sheet to read from an .xlsx file, the first by default
+      --header                 the first row holds column names";
+
+const STATS_HELP: &str = "\
+Usage: excel_takehome stats [options] [file]
+
+Prints the number of rows, columns, blank cells and formulas, and a guess at each
+column's type in the same format --schema reads.
+
+Options:
+      --sheet <name>           the sheet to read from an .xlsx file, the first by default
+      --header                 the first row holds column names";
+
+const GET_HELP: &str = "\
+Usage: excel_takehome get [options] [file] <cell or range>
+
+Prints the value of a cell, like B2, or of each cell in a range, like A1:C10, with formulas
+worked out. Exits with 1 if any of them is an error like #REF!.
+
+Options:
+      --format <format>        text (default), a row per line split by tabs, or json
+      --sheet <name>           the sheet to read from an .xlsx file, the first by default
+      --header                 the first row holds column names";
+
+const REPL_HELP: &str = "\
+Usage: excel_takehome repl [options] [file]
+
+Loads the file, or starts with an empty grid, and reads lines like =SUM(A1:A3), B2 or
+B2 = 42. Type :help once it's running for the rest.
+
+Options:
+      --sheet <name>           the sheet to read from an .xlsx file, the first by default
+      --header                 the first row holds column names";
+
+const VIEW_HELP: &str = "\
+Usage: excel_takehome view [options] [file]
+
+Draws the grid as a table with formulas worked out, highlighting formulas and errors. In a
+terminal it shows a page at a time: enter for the next page, b to go back, > and < to move
+across, a row number to jump to it and q to quit. Otherwise the whole table is printed.
+
+Options:
+      --from <row>             the first row to show, counting from 1
+      --rows <n>               rows on a page, $LINES or 40 by default
+      --width <n>              characters on a line, $COLUMNS or 120 by default
+      --cell-width <n>         longer cells are cut short, 20 by default
+      --color, --no-color      highlight or not, the default is to in a terminal
+      --sheet <name>           the sheet to read from an .xlsx file, the first by default
+      --header                 the first row holds column names";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Convert,
+    Eval,
+    Validate,
+    Stats,
+    Get,
+    Repl,
+    View,
+}
+
+impl Command {
+    pub fn help(&self) -> &'static str {
+        match self {
+            Command::Convert => CONVERT_HELP,
+            Command::Eval => EVAL_HELP,
+            Command::Validate => VALIDATE_HELP,
+            Command::Stats => STATS_HELP,
+            Command::Get => GET_HELP,
+            Command::Repl => REPL_HELP,
+            Command::View => VIEW_HELP,
+        }
+    }
+}
+
+// how get prints values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+// the parsed command line. options that weren't given are None, and mean whatever the file was
+// read with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Args {
+    pub command: Command,
+    pub input: String, // "-" for stdin.
+    pub output: String, // "-" for stdout.
+    pub delimiter: Option<char>,
+    pub quoting: Option<Quoting>,
+    pub line_terminator: Option<LineTerminator>,
+    pub mode: OutputMode,
+    pub schema: Option<String>,
+    pub header: bool,
+    pub sheet: Option<String>, // for .xlsx files, None is the first sheet.
+    pub range: Option<(CellAddress, CellAddress)>, // the cells get prints, a single cell is both ends.
+    pub format: Format,
+    // the rest are for view, `from` is zero based.
+    pub from: usize,
+    pub rows: Option<usize>,
+    pub width: Option<usize>,
+    pub cell_width: Option<usize>,
+    pub color: Option<bool>,
+}
+
+// what the command line asked for, --help is asked for rather than being an error.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Invocation {
+    Run(Args),
+    Help(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgsError {
+    MissingCommand,
+    UnknownCommand(String),
+    UnknownOption(String),
+    MissingValue(String), // the option that needs one.
+    MissingReference,
+    InvalidValue { option: String, value: String },
+    ExtraArgument(String),
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgsError::MissingCommand => write!(f, "Missing a command"),
+            ArgsError::UnknownCommand(command) => write!(f, "Unknown command '{}'", command),
+            ArgsError::UnknownOption(option) => write!(f, "Unknown option '{}'", option),
+            ArgsError::MissingValue(option) => write!(f, "Option '{}' needs a value", option),
+            ArgsError::MissingReference => write!(f, "Missing a cell or range to get"),
+            ArgsError::InvalidValue { option, value } => write!(f, "Invalid value '{}' for '{}'", value, option),
+            ArgsError::ExtraArgument(argument) => write!(f, "Unexpected argument '{}'", argument),
+        }
+    }
+}
+
+// parses the arguments after the executable name.
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Invocation, ArgsError> {
+    let mut args = args.into_iter();
+    let command = match args.next().as_deref() {
+        Some("convert") => Command::Convert,
+        Some("eval") => Command::Eval,
+        Some("validate") => Command::Validate,
+        Some("stats") => Command::Stats,
+        Some("get" | "query") => Command::Get,
+        Some("repl") => Command::Repl,
+        Some("view") => Command::View,
+        Some("-h" | "--help" | "help") => return Ok(Invocation::Help(USAGE)),
+        Some(command) => return Err(ArgsError::UnknownCommand(command.to_string())),
+        None => return Err(ArgsError::MissingCommand),
+    };
+
+    let mut parsed = Args {
+        command,
+        input: "-".to_string(),
+        output: "-".to_string(),
+        delimiter: None,
+        quoting: None,
+        line_terminator: None,
+        mode: if command == Command::Eval { OutputMode::Values } else { OutputMode::Formulas },
+        schema: None,
+        header: false,
+        sheet: None,
+        range: None,
+        format: Format::Text,
+        from: 0,
+        rows: None,
+        width: None,
+        cell_width: None,
+        color: None,
+    };
+    let writes = matches!(command, Command::Convert | Command::Eval);
+    let mut positional = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| ArgsError::MissingValue(arg.clone()));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Invocation::Help(command.help())),
+            "--header" => parsed.header = true,
+            "--sheet" => parsed.sheet = Some(value()?),
+            "--schema" if matches!(command, Command::Convert | Command::Validate) => parsed.schema = Some(value()?),
+            "-o" | "--output" if writes => parsed.output = value()?,
+            "-d" | "--delimiter" if writes => {
+                let value = value()?;
+                parsed.delimiter = Some(parse_delimiter(&value).ok_or_else(|| invalid(&arg, &value))?);
+            }
+            "--quoting" if writes => {
+                let value = value()?;
+                parsed.quoting = Some(parse_quoting(&value).ok_or_else(|| invalid(&arg, &value))?);
+            }
+            "--line-terminator" if writes => {
+                let value = value()?;
+                parsed.line_terminator = Some(parse_line_terminator(&value).ok_or_else(|| invalid(&arg, &value))?);
+            }
+            "--mode" if writes => {
+                let value = value()?;
+                parsed.mode = parse_mode(&value).ok_or_else(|| invalid(&arg, &value))?;
+            }
+            "--format" if command == Command::Get => {
+                let value = value()?;
+                parsed.format = parse_format(&value).ok_or_else(|| invalid(&arg, &value))?;
+            }
+            "--from" if command == Command::View => {
+                let value = value()?;
+                let row = value.parse::<usize>().ok().filter(|row| *row > 0);
+                parsed.from = row.ok_or_else(|| invalid(&arg, &value))? - 1;
+            }
+            "--rows" | "--width" | "--cell-width" if command == Command::View => {
+                let value = value()?;
+                let number = value.parse::<usize>().ok().filter(|number| *number > 0);
+                let number = Some(number.ok_or_else(|| invalid(&arg, &value))?);
+                match arg.as_str() {
+                    "--rows" => parsed.rows = number,
+                    "--width" => parsed.width = number,
+                    _ => parsed.cell_width = number,
+                }
+            }
+            "--color" if command == Command::View => parsed.color = Some(true),
+            "--no-color" if command == Command::View => parsed.color = Some(false),
+            option if option.starts_with('-') && option != "-" => return Err(ArgsError::UnknownOption(arg)),
+            _ => positional.push(arg),
+        }
+    }
+
+    // get always ends with the reference, the file before it is optional like everywhere else.
+    if command == Command::Get {
+        let reference = positional.pop().ok_or(ArgsError::MissingReference)?;
+        parsed.range = Some(parse_range(&reference).ok_or_else(|| invalid("reference", &reference))?);
+    }
+    let mut positional = positional.into_iter();
+    if let Some(input) = positional.next() {
+        parsed.input = input;
+    }
+    if let Some(extra) = positional.next() {
+        return Err(ArgsError::ExtraArgument(extra));
+    }
+    Ok(Invocation::Run(parsed))
+}
+
+// "B2" or "A1:C10".
+pub fn parse_range(reference: &str) -> Option<(CellAddress, CellAddress)> {
+    match reference.split_once(':') {
+        Some((start, end)) => Some((CellAddress::parse(start)?, CellAddress::parse(end)?)),
+        None => CellAddress::parse(reference).map(|address| (address, address)),
+    }
+}
+
+fn invalid(option: &str, value: &str) -> ArgsError {
+    ArgsError::InvalidValue {
+        option: option.to_string(),
+        value: value.to_string(),
+    }
+}
+
+// a single ascii character, or tab spelled out since it's awkward to type in a shell.
+fn parse_delimiter(value: &str) -> Option<char> {
+    match value {
+        "tab" | "\\t" => Some('\t'),
+        _ => {
+            let mut chars = value.chars();
+            let delimiter = chars.next()?;
+            (chars.next().is_none() && delimiter.is_ascii()).then_some(delimiter)
+        }
+    }
+}
+
+fn parse_quoting(value: &str) -> Option<Quoting> {
+    match value {
+        "minimal" => Some(Quoting::Minimal),
+        "all" => Some(Quoting::All),
+        "non-numeric" => Some(Quoting::NonNumeric),
+        "never" => Some(Quoting::Never),
+        _ => None,
+    }
+}
+
+fn parse_line_terminator(value: &str) -> Option<LineTerminator> {
+    match value {
+        "lf" => Some(LineTerminator::LF),
+        "crlf" => Some(LineTerminator::CRLF),
+        "cr" => Some(LineTerminator::CR),
+        _ => None,
+    }
+}
+
+fn parse_format(value: &str) -> Option<Format> {
+    match value {
+        "text" => Some(Format::Text),
+        "json" => Some(Format::Json),
+        _ => None,
+    }
+}
+
+fn parse_mode(value: &str) -> Option<OutputMode> {
+    match value {
+        "formulas" => Some(OutputMode::Formulas),
+        "values" => Some(OutputMode::Values),
+        "both" => Some(OutputMode::Both),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Invocation, ArgsError> {
+        parse_args(args.split_whitespace().map(str::to_string))
+    }
+
+    fn run(args: &str) -> Args {
+        match parse(args) {
+            Ok(Invocation::Run(args)) => args,
+            other => panic!("expected a command, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = run("convert data.csv -o out.tsv -d tab --quoting all --line-terminator crlf");
+        assert_eq!(args.command, Command::Convert);
+        assert_eq!(args.input, "data.csv");
+        assert_eq!(args.output, "out.tsv");
+        assert_eq!(args.delimiter, Some('\t'));
+        assert_eq!(args.quoting, Some(Quoting::All));
+        assert_eq!(args.line_terminator, Some(LineTerminator::CRLF));
+        assert_eq!(args.mode, OutputMode::Formulas);
+
+        // stdin and stdout unless told otherwise, eval writes values.
+        let args = run("eval");
+        assert_eq!((args.input.as_str(), args.output.as_str()), ("-", "-"));
+        assert_eq!(args.mode, OutputMode::Values);
+        assert_eq!(run("eval - --mode both").mode, OutputMode::Both);
+
+        let args = run("validate --schema types.txt --header data.csv");
+        assert_eq!(args.schema.as_deref(), Some("types.txt"));
+        assert!(args.header);
+
+        let args = run("get data.csv A1:C10 --format json");
+        assert_eq!(args.input, "data.csv");
+        assert_eq!(args.range, Some((CellAddress { row: 0, col: 0 }, CellAddress { row: 9, col: 2 })));
+        assert_eq!(args.format, Format::Json);
+        assert_eq!(run("get book.xlsx --sheet Summary A1").sheet.as_deref(), Some("Summary"));
+        let args = run("get b2");
+        assert_eq!(args.input, "-");
+        assert_eq!(args.range, Some((CellAddress { row: 1, col: 1 }, CellAddress { row: 1, col: 1 })));
+
+        let args = run("view medium.csv --from 100 --rows 20 --cell-width 8 --no-color");
+        assert_eq!((args.from, args.rows, args.width), (99, Some(20), None));
+        assert_eq!((args.cell_width, args.color), (Some(8), Some(false)));
+
+        assert_eq!(parse("--help"), Ok(Invocation::Help(USAGE)));
+        assert_eq!(parse("stats --help"), Ok(Invocation::Help(STATS_HELP)));
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        assert_eq!(parse(""), Err(ArgsError::MissingCommand));
+        assert_eq!(parse("data.csv"), Err(ArgsError::UnknownCommand("data.csv".to_string())));
+        assert_eq!(parse("convert -x"), Err(ArgsError::UnknownOption("-x".to_string())));
+        // options only go with the commands they make sense for.
+        assert_eq!(parse("stats -o out.csv"), Err(ArgsError::UnknownOption("-o".to_string())));
+        assert_eq!(parse("convert -o"), Err(ArgsError::MissingValue("-o".to_string())));
+        assert_eq!(parse("convert a.csv b.csv"), Err(ArgsError::ExtraArgument("b.csv".to_string())));
+        assert_eq!(
+            parse("convert --quoting some"),
+            Err(ArgsError::InvalidValue { option: "--quoting".to_string(), value: "some".to_string() })
+        );
+        assert!(matches!(parse("convert -d ab"), Err(ArgsError::InvalidValue { .. })));
+        // the csv parser works on bytes, so dialect characters have to be ascii.
+        assert!(matches!(parse("convert -d §"), Err(ArgsError::InvalidValue { .. })));
+        assert_eq!(parse("get"), Err(ArgsError::MissingReference));
+        assert!(matches!(parse("view --from 0"), Err(ArgsError::InvalidValue { .. })));
+        assert!(matches!(parse("view --rows x"), Err(ArgsError::InvalidValue { .. })));
+        assert!(matches!(parse("get data.csv A1:"), Err(ArgsError::InvalidValue { .. })));
+        assert_eq!(parse("get a.csv b.csv A1"), Err(ArgsError::ExtraArgument("b.csv".to_string())));
+    }
+}
@@ -0,0 +1,368 @@
+use crate::csv::{parse_parallel, sniff, Dialect, Rows};
+use crate::decimal::Decimal;
+use crate::excel::{CellAddress, Excel, FieldValue, Grid, Value};
+use crate::interner::Interner;
+use crate::source::Source;
+
+// one bit per row.
+#[derive(Debug, Default)]
+struct Bitmap {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Bitmap {
+    fn push(&mut self, bit: bool) {
+        if self.len.is_multiple_of(64) {
+            self.words.push(0);
+        }
+        if bit {
+            self.words[self.len / 64] |= 1 << (self.len % 64);
+        }
+        self.len += 1;
+    }
+
+    fn get(&self, idx: usize) -> bool {
+        self.words[idx / 64] & (1 << (idx % 64)) != 0
+    }
+}
+
+// the values of a column, as one vector of whatever type every cell in it has. a column with more
+// than one type of value falls back to keeping each cell's FieldValue. empty cells hold a 0 or the
+// like so the vectors line up with the rows.
+#[derive(Debug)]
+pub enum ColumnValues {
+    Empty, // nothing but blank cells so far.
+    Integer(Vec<i64>),
+    Float(Vec<f64>),
+    Decimal(Vec<Decimal>),
+    Text(Vec<u32>), // ids from the grid's interner.
+    Mixed(Vec<FieldValue>),
+}
+
+#[derive(Debug)]
+pub struct Column {
+    pub values: ColumnValues,
+    empty: Bitmap, // cells that are blank, or missing because their row is too short.
+}
+
+impl Column {
+    fn new() -> Self {
+        Self {
+            values: ColumnValues::Empty,
+            empty: Bitmap::default(),
+        }
+    }
+
+    fn push(&mut self, value: Option<Value>, interner: &mut Interner) {
+        let value = match value {
+            Some(Value::Empty) | None => {
+                self.push_placeholder();
+                self.empty.push(true);
+                return;
+            }
+            Some(value) => value,
+        };
+
+        self.empty.push(false);
+        match (&mut self.values, value) {
+            (ColumnValues::Integer(values), Value::Integer(i)) => values.push(i),
+            (ColumnValues::Float(values), Value::Float(fl)) => values.push(fl),
+            (ColumnValues::Decimal(values), Value::Decimal(d)) => values.push(d),
+            (ColumnValues::Text(values), Value::String(s)) => values.push(interner.intern(s)),
+            (ColumnValues::Mixed(values), value) => values.push(value.into()),
+            (ColumnValues::Empty, value) => {
+                // every cell before this one was empty, fill them in with the right kind of nothing.
+                let len = self.empty.len - 1;
+                self.values = match value {
+                    Value::Integer(i) => ColumnValues::Integer(placeholders(len, 0, i)),
+                    Value::Float(fl) => ColumnValues::Float(placeholders(len, 0.0, fl)),
+                    Value::Decimal(d) => ColumnValues::Decimal(placeholders(len, Decimal::default(), d)),
+                    Value::String(s) => ColumnValues::Text(placeholders(len, 0, interner.intern(s))),
+                    value => ColumnValues::Mixed(placeholders_with(len, value.into())),
+                };
+            }
+            (_, value) => {
+                let mut mixed = self.to_mixed(interner);
+                mixed.push(value.into());
+                self.values = ColumnValues::Mixed(mixed);
+            }
+        }
+    }
+
+    fn push_placeholder(&mut self) {
+        match &mut self.values {
+            ColumnValues::Empty => {}
+            ColumnValues::Integer(values) => values.push(0),
+            ColumnValues::Float(values) => values.push(0.0),
+            ColumnValues::Decimal(values) => values.push(Decimal::default()),
+            ColumnValues::Text(values) => values.push(0),
+            ColumnValues::Mixed(values) => values.push(FieldValue::Empty),
+        }
+    }
+
+    fn to_mixed(&self, interner: &Interner) -> Vec<FieldValue> {
+        match &self.values {
+            ColumnValues::Empty => Vec::new(),
+            ColumnValues::Integer(values) => values.iter().map(|i| FieldValue::Integer(*i)).collect(),
+            ColumnValues::Float(values) => values.iter().map(|fl| FieldValue::Float(*fl)).collect(),
+            ColumnValues::Decimal(values) => values.iter().map(|d| FieldValue::Decimal(*d)).collect(),
+            ColumnValues::Text(values) => values
+                .iter()
+                .map(|id| Value::String(interner.get(*id)).into())
+                .collect(),
+            ColumnValues::Mixed(_) => unreachable!("a mixed column never has to be converted"),
+        }
+    }
+
+    fn value<'a>(&'a self, row: usize, interner: &'a Interner) -> Value<'a> {
+        if self.empty.get(row) {
+            return Value::Empty;
+        }
+        match &self.values {
+            ColumnValues::Empty => Value::Empty,
+            ColumnValues::Integer(values) => Value::Integer(values[row]),
+            ColumnValues::Float(values) => Value::Float(values[row]),
+            ColumnValues::Decimal(values) => Value::Decimal(values[row]),
+            ColumnValues::Text(values) => Value::String(interner.get(values[row])),
+            ColumnValues::Mixed(values) => values[row].as_value(),
+        }
+    }
+}
+
+fn placeholders<T: Copy>(len: usize, placeholder: T, value: T) -> Vec<T> {
+    let mut values = vec![placeholder; len];
+    values.push(value);
+    values
+}
+
+fn placeholders_with(len: usize, value: FieldValue) -> Vec<FieldValue> {
+    let mut values: Vec<FieldValue> = (0..len).map(|_| FieldValue::Empty).collect();
+    values.push(value);
+    values
+}
+
+// the same grid as Excel but stored a column at a time: wide files of numbers take a fraction of the
+// memory, and sums or ranges down a column read through one tightly packed vector. with a header the
+// first row is kept on its own, so the names on top don't turn every column of numbers mixed. the
+// grid still reads it back as row 0, the same as Excel does.
+pub struct Columnar {
+    pub header: Option<Vec<FieldValue>>,
+    pub columns: Vec<Column>,
+    row_lens: Vec<u32>, // how many cells each row actually has, the columns are padded out past that.
+    interner: Interner,
+    pub dialect: Dialect,
+}
+
+impl Columnar {
+    pub fn from_rows(csv: Rows, dialect: Dialect) -> Self {
+        let mut builder = Builder::new(dialect);
+        for row in &csv {
+            builder.push_row(row.iter().map(|item| Value::from_csv(item, dialect)));
+        }
+        builder.finish()
+    }
+
+    pub fn from_excel(excel: &Excel) -> Self {
+        let mut builder = Builder::new(excel.dialect);
+        for row in &excel.rows {
+            builder.push_row(row.iter().map(|field| field.val.as_value()));
+        }
+        builder.finish()
+    }
+
+    pub fn from_path(path: &str) -> std::io::Result<Self> {
+        let source = Source::open(path)?;
+        let dialect = sniff(&source);
+        Ok(Self::from_rows(parse_parallel(&source, dialect)?, dialect))
+    }
+
+    pub fn column(&self, col: usize) -> Option<&Column> {
+        self.columns.get(col)
+    }
+}
+
+impl Grid for Columnar {
+    fn value(&self, address: CellAddress) -> Option<Value<'_>> {
+        let row = match (&self.header, address.row) {
+            (Some(header), 0) => return header.get(address.col).map(FieldValue::as_value),
+            (Some(_), row) => row - 1,
+            (None, row) => row,
+        };
+        let row_len = *self.row_lens.get(row)? as usize;
+        if address.col >= row_len {
+            return None;
+        }
+        Some(self.columns[address.col].value(row, &self.interner))
+    }
+
+    fn row_count(&self) -> usize {
+        self.row_lens.len() + self.header.is_some() as usize
+    }
+
+    fn column_count(&self) -> usize {
+        self.columns.len().max(self.header.as_ref().map_or(0, Vec::len))
+    }
+
+    // empty cells hold zeros so typed columns can be summed straight through.
+    fn column_sum(&self, col: usize) -> f64 {
+        let header = self
+            .header
+            .as_ref()
+            .and_then(|header| header.get(col)?.as_value().as_f64())
+            .unwrap_or(0.0);
+        let column = match self.columns.get(col) {
+            Some(column) => column,
+            None => return header,
+        };
+        header + match &column.values {
+            ColumnValues::Integer(values) => values.iter().map(|i| *i as f64).sum(),
+            ColumnValues::Float(values) => values.iter().sum(),
+            ColumnValues::Decimal(values) => values.iter().map(|d| d.to_f64()).sum(),
+            ColumnValues::Mixed(values) => values
+                .iter()
+                .enumerate()
+                .filter(|(row, _)| !column.empty.get(*row))
+                .filter_map(|(_, value)| value.as_value().as_f64())
+                .sum(),
+            ColumnValues::Empty | ColumnValues::Text(_) => 0.0,
+        }
+    }
+}
+
+struct Builder {
+    header: Option<Vec<FieldValue>>,
+    columns: Vec<Column>,
+    row_lens: Vec<u32>,
+    interner: Interner,
+    dialect: Dialect,
+}
+
+impl Builder {
+    fn new(dialect: Dialect) -> Self {
+        Self {
+            header: None,
+            columns: Vec::new(),
+            row_lens: Vec::new(),
+            interner: Interner::new(),
+            dialect,
+        }
+    }
+
+    fn push_row<'a>(&mut self, row: impl Iterator<Item = Value<'a>>) {
+        if self.dialect.has_header && self.header.is_none() {
+            self.header = Some(row.map(FieldValue::from).collect());
+            return;
+        }
+
+        let mut len = 0;
+        for value in row {
+            if len == self.columns.len() {
+                // a wider row than any before it, the new column is empty for all the rows above.
+                let mut column = Column::new();
+                for _ in 0..self.row_lens.len() {
+                    column.push(None, &mut self.interner);
+                }
+                self.columns.push(column);
+            }
+            self.columns[len].push(Some(value), &mut self.interner);
+            len += 1;
+        }
+
+        for column in &mut self.columns[len..] {
+            column.push(None, &mut self.interner);
+        }
+        self.row_lens.push(len as u32);
+    }
+
+    fn finish(self) -> Columnar {
+        Columnar {
+            header: self.header,
+            columns: self.columns,
+            row_lens: self.row_lens,
+            interner: self.interner,
+            dialect: self.dialect,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_same_cells(excel: &Excel, columnar: &Columnar) {
+        for row in 0..excel.row_count() + 1 {
+            for col in 0..excel.column_count() + 1 {
+                let address = CellAddress { row, col };
+                assert_eq!(columnar.value(address), excel.value(address), "{:?}", address);
+            }
+        }
+    }
+
+    #[test]
+    fn test_same_cells_as_excel() {
+        for path in ["test_data/medium.csv", "test_data/formulas.csv", "test_data/escapes_from_excel.csv"] {
+            let excel = Excel::from_path(path).unwrap();
+            assert_same_cells(&excel, &Columnar::from_path(path).unwrap());
+            assert_same_cells(&excel, &Columnar::from_excel(&excel));
+        }
+
+        // ragged rows, columns that start out empty and one that changes type part way down.
+        let dialect = Dialect { empty_strings: true, ..Dialect::default() };
+        let excel = Excel::with_dialect("a,\"\"\n,x\n".to_string(), dialect).unwrap();
+        let columnar = Columnar::from_excel(&excel);
+        assert_same_cells(&excel, &columnar);
+        assert!(matches!(columnar.value(CellAddress { row: 0, col: 1 }), Some(Value::String(""))));
+        assert!(matches!(columnar.value(CellAddress { row: 1, col: 0 }), Some(Value::Empty)));
+
+        let excel = Excel::new("a,,1\nb,2\nc,3,,4\n,4,2.5\n".to_string());
+        let columnar = Columnar::from_excel(&excel);
+        assert_same_cells(&excel, &columnar);
+        assert!(matches!(columnar.columns[0].values, ColumnValues::Text(_)));
+        assert!(matches!(columnar.columns[1].values, ColumnValues::Integer(_)));
+        assert!(matches!(columnar.columns[2].values, ColumnValues::Mixed(_)));
+        assert!(matches!(columnar.columns[3].values, ColumnValues::Integer(_)));
+    }
+
+    #[test]
+    fn test_typed_columns() {
+        // every field in medium.csv is quoted, so it's all text unless quoted numbers are asked for.
+        let columnar = Columnar::from_path("test_data/medium.csv").unwrap();
+        assert!(matches!(columnar.column(2).unwrap().values, ColumnValues::Text(_)));
+        let dialect = Dialect { quoted_numbers: true, has_header: true, ..Dialect::default() };
+        let excel = Excel::from_path_with_dialect("test_data/medium.csv", dialect).unwrap();
+        let columnar = Columnar::from_excel(&excel);
+        // the header is kept to the side, so the columns under it are only numbers.
+        assert!(matches!(columnar.column(2).unwrap().values, ColumnValues::Float(_)));
+        assert!(matches!(columnar.column(7).unwrap().values, ColumnValues::Integer(_)));
+        assert_eq!(columnar.header.as_ref().unwrap()[2].to_string(), "LATITUDE");
+        assert_same_cells(&excel, &columnar);
+
+        // without one, the names on top make them mixed.
+        let dialect = Dialect { quoted_numbers: true, ..Dialect::default() };
+        let columnar = Columnar::from_excel(&Excel::from_path_with_dialect("test_data/medium.csv", dialect).unwrap());
+        assert!(matches!(columnar.column(2).unwrap().values, ColumnValues::Mixed(_)));
+
+        let rows = vec![vec!["1".into(), "a".into(), "0.5".into()], vec!["2".into(), "a".into(), "".into()]];
+        let columnar = Columnar::from_rows(rows, Dialect::default());
+        assert!(matches!(&columnar.column(0).unwrap().values, ColumnValues::Integer(values) if values == &[1, 2]));
+        assert!(matches!(&columnar.column(1).unwrap().values, ColumnValues::Text(ids) if ids[0] == ids[1]));
+        assert!(matches!(columnar.column(2).unwrap().values, ColumnValues::Float(_)));
+        assert_eq!(columnar.interner.len(), 1);
+    }
+
+    #[test]
+    fn test_column_sum_and_range() {
+        let dialect = Dialect { quoted_numbers: true, ..Dialect::default() };
+        let excel = Excel::from_path_with_dialect("test_data/medium.csv", dialect).unwrap();
+        let columnar = Columnar::from_excel(&excel);
+        for col in 0..excel.column_count() {
+            let (expected, sum) = (excel.column_sum(col), columnar.column_sum(col));
+            assert!((expected - sum).abs() <= expected.abs() * 1e-9, "column {}", col);
+        }
+
+        let start = CellAddress::parse("B2").unwrap();
+        let end = CellAddress::parse("D40").unwrap();
+        assert_eq!(columnar.range(start, end), excel.range(start, end));
+    }
+}
@@ -1,194 +1,973 @@
-use std::fs::{read_to_string, File};
-use std::io::{BufWriter, Write};
+use std::borrow::Cow;
 use std::fmt;
+use std::ops::Deref;
+use scan::Specials;
+
+mod error;
+mod parallel;
+mod reader;
+mod scan;
+mod sniffer;
+
+pub use error::{CsvError, Position};
+pub use parallel::{map_chunks, parse_parallel, split_records};
+pub use reader::{Reader, MAX_RECORD_SIZE};
+pub use sniffer::sniff;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineTerminator {
+    LF, // "\n"
+    CRLF, // "\r\n"
+    CR, // "\r", old mac style files.
+}
+
+impl LineTerminator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineTerminator::LF => "\n",
+            LineTerminator::CRLF => "\r\n",
+            LineTerminator::CR => "\r",
+        }
+    }
+}
+
+// which fields get quotes when writing. whatever the policy, a blank cell is written as nothing and
+// any field that couldn't be read back the same without quotes gets them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Quoting {
+    // only fields that need them. fields read from a file that haven't been changed are also
+    // written exactly as they were read.
+    #[default]
+    Minimal,
+    // every field, numbers included. they'll need reading back with quoted_numbers set, and text
+    // that looks like a number can't be told apart from a number.
+    All,
+    // everything except numbers.
+    NonNumeric,
+    // no field, special characters are escaped with the dialect's escape character instead.
+    // with no escape character a field holding one is quoted after all.
+    Never,
+}
+
+// describes the flavour of csv being read or written, the default is plain comma separated values.
+// "\n" and "\r\n" always end a row when reading, a lone "\r" only does when the terminator is CR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dialect {
+    pub delimiter: char,
+    pub quote: char,
+    pub escape: Option<char>, // when None a quote inside a qualified string is escaped by doubling it.
+    pub line_terminator: LineTerminator,
+    pub has_header: bool, // the first row holds column names rather than data.
+    pub trim: bool, // strip leading and trailing spaces from the unquoted parts of a field.
+    pub decimals: bool, // read numbers with a decimal point as exact decimals rather than floats.
+    // quoted fields that look like numbers are read as numbers too. off by default, quoting "00123"
+    // is how a zip code or part number says it's text.
+    pub quoted_numbers: bool,
+    // a quoted empty field, "", is an empty string rather than a blank cell like an unquoted one is.
+    pub empty_strings: bool,
+    pub quoting: Quoting, // only matters for writing.
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            quote: '"',
+            escape: None,
+            line_terminator: LineTerminator::LF,
+            has_header: false,
+            trim: false,
+            decimals: false,
+            quoted_numbers: false,
+            empty_strings: false,
+            quoting: Quoting::Minimal,
+        }
+    }
+}
+
+impl Dialect {
+    // wraps the field in quotes if it contains anything that would otherwise be read back differently.
+    pub fn quote_field(&self, item: &str) -> String {
+        if self.needs_quotes(item) {
+            self.quote(item)
+        } else {
+            item.to_string()
+        }
+    }
+
+    pub fn needs_quotes(&self, item: &str) -> bool {
+        item.chars().any(|c| {
+            c == self.delimiter || c == self.quote || c == '\n' || c == '\r' || Some(c) == self.escape
+        })
+    }
+
+    // puts the escape character in front of every special character instead of quoting the field,
+    // only for dialects that have one.
+    pub fn escape_field(&self, item: &str) -> String {
+        let mut out = String::with_capacity(item.len());
+        for c in item.chars() {
+            if let Some(escape) = self.escape {
+                if c == self.delimiter || c == self.quote || c == escape || c == '\n' || c == '\r' {
+                    out.push(escape);
+                }
+            }
+            out.push(c);
+        }
+        out
+    }
+
+    // always wraps the field in quotes, escaping any quote or escape characters inside it.
+    pub fn quote(&self, item: &str) -> String {
+        let mut out = String::with_capacity(item.len() + 2);
+        out.push(self.quote);
+        for c in item.chars() {
+            if let Some(escape) = self.escape {
+                if c == self.quote || c == escape {
+                    out.push(escape);
+                }
+            } else if c == self.quote {
+                out.push(self.quote);
+            }
+            out.push(c);
+        }
+        out.push(self.quote);
+        out
+    }
+}
 
 #[derive(Debug)]
 enum Token<'a> {
-    Value(&'a [char]), // a full field
+    Value(&'a [u8]), // a full field
     Delimiter, // comma
     Newline, // "\n" | "\r\n"
     TextQualifier, // '"'
-    WhiteSpace,// whitespace not inside a text qualified string...
     Escaped, // '""' for quotations inside a qualified string.
+    Literal(u8), // a byte following the dialect's escape character, e.g. '\"'.
     EndOfFile,
 }
 
+// a spot in the source the tokenizer can be reset to.
+#[derive(Debug, Clone, Copy)]
+struct Mark {
+    offset: usize,
+    line: usize,
+    line_start: usize,
+}
 
+// works on the raw bytes of the source, every special character in a dialect has to be ascii so
+// they can never show up in the middle of a multi byte utf-8 character. a dialect with any that
+// aren't is an InvalidDialect error.
 pub struct Tokenizer<'a> {
-    view: &'a [char],
+    source: &'a [u8],
+    offset: usize,
+    delimiter: u8,
+    quote: u8,
+    escape: Option<u8>,
+    cr_terminator: bool,
+    special: Specials, // bytes that end an unquoted value.
+    // '""' only means an escaped quote inside a qualified string, at the start of a field it's
+    // an opening and closing quote.
+    in_qualifier: bool,
+    // line and column are only worked out when a position is asked for, the tokenizer just keeps
+    // track of which line it's on and where that line started.
+    line: usize,
+    line_start: usize,
+    origin: Position, // where the source starts, for sources that are part of a bigger stream.
 }
 
 // this turned into more of a parse3r than a tokenizer...
 impl<'a> Tokenizer<'a> {
-    pub fn new(view: &'a [char]) -> Self {
-        Self {
-            view,
-        }
+    pub fn new(source: &'a [u8], dialect: Dialect) -> Result<Self, CsvError> {
+        let delimiter = ascii_byte(dialect.delimiter)?;
+        let quote = ascii_byte(dialect.quote)?;
+        let escape = dialect.escape.map(ascii_byte).transpose()?;
+
+        let special = Specials::new([b'\n', b'\r', delimiter, quote, escape.unwrap_or(quote)]);
+
+        Ok(Self {
+            source,
+            offset: 0,
+            delimiter,
+            quote,
+            escape,
+            cr_terminator: dialect.line_terminator == LineTerminator::CR,
+            special,
+            in_qualifier: false,
+            line: 1,
+            line_start: 0,
+            origin: Position::default(),
+        })
     }
 
     fn skip(&mut self, n: usize) {
-        self.view = &self.view[n.min(self.view.len())..];
+        self.offset = (self.offset + n).min(self.source.len());
     }
 
-    fn peek(&self) -> Option<&char> {
-        self.view.get(0)
+    fn peek(&self) -> Option<u8> {
+        self.source.get(self.offset).copied()
     }
 
-    fn next_is(&self, cmp: char) -> bool {
-        if let Some(c) = self.peek() {
-            return *c == cmp;
-        }
-        false
+    fn next_is(&self, cmp: u8) -> bool {
+        self.peek() == Some(cmp)
     }
 
-    fn next_is_one_of(&self, sp_cmp: &[char]) -> bool {
-        if let Some(c) = self.peek() {
-            for cmp in sp_cmp {
-                if *c == *cmp {
-                    return true
-                }
+    fn empty(&self) -> bool {
+        self.offset >= self.source.len()
+    }
+
+    fn position(&self) -> Position {
+        let column = count_chars(&self.source[self.line_start..self.offset]) + 1;
+        if self.line == 1 {
+            Position {
+                line: self.origin.line,
+                column: self.origin.column + column - 1,
+                byte: self.origin.byte + self.offset,
+            }
+        } else {
+            Position {
+                line: self.origin.line + self.line - 1,
+                column,
+                byte: self.origin.byte + self.offset,
             }
         }
-        false
     }
 
-    fn empty(&self) -> bool {
-        self.view.is_empty()
+    fn mark(&self) -> Mark {
+        Mark {
+            offset: self.offset,
+            line: self.line,
+            line_start: self.line_start,
+        }
     }
 
-    // to-do, could this be a zero copy operation instead?
-    fn get_csv_token(&mut self) -> Token {
-        if self.empty() {
-            return Token::EndOfFile;
+    // goes back to `mark` and then on to the start of the following line, returning the line skipped.
+    fn rewind_to_line_end(&mut self, mark: Mark) -> &'a [u8] {
+        self.offset = mark.offset;
+        self.line = mark.line;
+        self.line_start = mark.line_start;
+        self.in_qualifier = false;
+
+        let line_break = if self.cr_terminator { b'\r' } else { b'\n' };
+        let rest = &self.source[mark.offset..];
+        match rest.iter().position(|byte| *byte == line_break) {
+            Some(idx) => {
+                self.skip(idx + 1);
+                self.line += 1;
+                self.line_start = self.offset;
+                &rest[..idx + 1]
+            }
+            None => {
+                self.skip(rest.len());
+                rest
+            }
         }
+    }
 
-        if let Some(c) = self.peek() {
-            match c {
-                '"' => {
-                    self.skip(1);
-                    if self.next_is('"') {
+    fn newline(&mut self, len: usize) -> Token<'a> {
+        self.skip(len);
+        self.line += 1;
+        self.line_start = self.offset;
+        Token::Newline
+    }
+
+    // how far it is to the next byte that could end an unquoted value.
+    fn value_len(&self) -> usize {
+        let rest = &self.source[self.offset..];
+        self.special.find(rest).unwrap_or(rest.len())
+    }
+
+    fn get_csv_token(&mut self) -> Token<'a> {
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Token::EndOfFile,
+        };
+
+        match c {
+            c if Some(c) == self.escape => {
+                self.skip(1);
+                match self.peek() {
+                    Some(escaped) => {
                         self.skip(1);
-                        return Token::Escaped;
+                        Token::Literal(escaped)
                     }
-
-                    return Token::TextQualifier
+                    None => Token::EndOfFile,
                 }
-    
-                ',' => {
-                    self.skip(1);
-                    return Token::Delimiter; 
-                }
-    
-                ' ' => {
+            }
+
+            c if c == self.quote => {
+                self.skip(1);
+                if self.in_qualifier && self.next_is(self.quote) {
                     self.skip(1);
-                    return Token::WhiteSpace; 
-                }
-    
-    
-                '\r'  => {
-                    self.skip(2);
-                    return Token::Newline;
+                    return Token::Escaped;
                 }
-    
-                '\n' => {
+
+                self.in_qualifier = !self.in_qualifier;
+                Token::TextQualifier
+            }
+
+            c if c == self.delimiter => {
+                self.skip(1);
+                Token::Delimiter
+            }
+
+            b'\r' if self.source.get(self.offset + 1) == Some(&b'\n') => self.newline(2),
+
+            b'\r' if self.cr_terminator => self.newline(1),
+
+            b'\n' => self.newline(1),
+
+            _ => {
+                let start = self.offset;
+                // a lone '\r' that isn't a line terminator is just part of the value.
+                if c == b'\r' {
                     self.skip(1);
-                    return Token::Newline
-                }
-    
-                _ => {
-                    let tmp = &self.view[0..];
-                    let mut idx = 0;
-                    let sp_c = ['\n', '\r', ',', '"', ' '];
-                    while !self.next_is_one_of(&sp_c) {
-                        self.skip(1);
-                        idx += 1;
-                    }
-                    return Token::Value(&tmp[0..idx]);
                 }
+                let len = self.value_len();
+                self.skip(len);
+                Token::Value(&self.source[start..self.offset])
             }
         }
+    }
+}
 
-        Token::EndOfFile
+pub(crate) fn ascii_byte(c: char) -> Result<u8, CsvError> {
+    if c.is_ascii() {
+        Ok(c as u8)
+    } else {
+        Err(CsvError::InvalidDialect(c))
     }
 }
 
-pub struct Parser<'a> {
-    tokenizer: Tokenizer<'a>
+// the number of characters in some utf-8, without caring whether it's valid.
+fn count_chars(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|byte| *byte & 0xC0 != 0x80).count()
 }
 
-impl<'a> Parser<'a> {
-    pub fn new(source: &'a [char]) -> Self {
+// the bytes of the field being read. they are borrowed from the source for as long as the field is
+// one unbroken run of it, an escaped quote or escape character means it has to be copied.
+struct FieldBuilder {
+    start: usize,
+    end: usize,
+    owned: Option<Vec<u8>>,
+}
+
+impl FieldBuilder {
+    fn new() -> Self {
         Self {
-            tokenizer: Tokenizer::new(source)
+            start: 0,
+            end: 0,
+            owned: None,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn len(&self) -> usize {
+        match &self.owned {
+            Some(owned) => owned.len(),
+            None => self.end - self.start,
         }
     }
 
-    pub fn parse(&mut self) -> Vec<Vec<String>> {
-        let mut max_field_size = 0;
-        let mut max_row_width = 0;
+    fn clear(&mut self) {
+        self.start = 0;
+        self.end = 0;
+        self.owned = None;
+    }
+
+    fn bytes<'s>(&'s self, source: &'s [u8]) -> &'s [u8] {
+        match &self.owned {
+            Some(owned) => owned,
+            None => &source[self.start..self.end],
+        }
+    }
+
+    fn push_range(&mut self, source: &[u8], start: usize, end: usize) {
+        match &mut self.owned {
+            Some(owned) => owned.extend_from_slice(&source[start..end]),
+            None if self.start == self.end => {
+                self.start = start;
+                self.end = end;
+            }
+            None if self.end == start => self.end = end,
+            None => {
+                let mut owned = source[self.start..self.end].to_vec();
+                owned.extend_from_slice(&source[start..end]);
+                self.owned = Some(owned);
+            }
+        }
+    }
+
+    fn push_byte(&mut self, source: &[u8], byte: u8) {
+        let owned = self
+            .owned
+            .get_or_insert_with(|| source[self.start..self.end].to_vec());
+        owned.push(byte);
+    }
+}
+
+// a field's value along with exactly how it was written, quotes, escapes and padding included.
+// both borrow from the source when they can.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvField<'a> {
+    pub text: Cow<'a, str>,
+    pub raw: Cow<'a, [u8]>,
+    pub quoted: bool, // some part of the field was in quotes.
+}
+
+impl CsvField<'_> {
+    pub fn into_owned(self) -> CsvField<'static> {
+        CsvField {
+            text: Cow::Owned(self.text.into_owned()),
+            raw: Cow::Owned(self.raw.into_owned()),
+            quoted: self.quoted,
+        }
+    }
+}
+
+// an unquoted field, written exactly as its text.
+impl<'a> From<&'a str> for CsvField<'a> {
+    fn from(text: &'a str) -> Self {
+        Self {
+            text: Cow::Borrowed(text),
+            raw: Cow::Borrowed(text.as_bytes()),
+            quoted: false,
+        }
+    }
+}
+
+impl Deref for CsvField<'_> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.text
+    }
+}
+
+impl PartialEq<str> for CsvField<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.text == other
+    }
+}
+
+impl PartialEq<&str> for CsvField<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        self.text == *other
+    }
+}
+
+// a parsed row and where it started in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record<'a> {
+    pub fields: Vec<CsvField<'a>>,
+    pub position: Position,
+}
+
+// a record along with the raw text it came from and the first problem found while reading it.
+struct RawRecord<'a> {
+    record: Record<'a>,
+    raw: &'a [u8],
+    problem: Option<CsvError>,
+}
+
+// a row that was left out when parsing leniently.
+#[derive(Debug)]
+pub struct Warning {
+    pub row: usize, // 1 based count of the rows read so far, including bad ones.
+    pub problem: CsvError,
+    pub raw: Vec<u8>, // the source bytes of the row, including its line break.
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "row {}: {}: {:?}", self.row, self.problem, String::from_utf8_lossy(&self.raw))
+    }
+}
+
+pub type Rows<'a> = Vec<Vec<CsvField<'a>>>;
+
+pub struct Parser<'a> {
+    tokenizer: Tokenizer<'a>,
+    dialect: Dialect,
+    max_row_width: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(source: &'a [u8], dialect: Dialect) -> Result<Self, CsvError> {
+        Ok(Self {
+            tokenizer: Tokenizer::new(source, dialect)?,
+            dialect,
+            max_row_width: 0,
+        })
+    }
+
+    // never fails, malformed input is read as best it can be and invalid utf-8 is replaced.
+    pub fn parse(&mut self) -> Rows<'a> {
         let mut rows = Vec::new();
-        let mut current_row = Vec::new();
-        let mut current_field = String::new();
+        while let Some(raw) = self.next_record() {
+            rows.push(raw.record.fields);
+        }
+        rows
+    }
+
+    // rfc 4180 parsing, the first malformed quote or row with a different number of fields to the
+    // first row is an error.
+    pub fn parse_strict(&mut self) -> Result<Rows<'a>, CsvError> {
+        let mut rows: Rows<'a> = Vec::new();
+        while let Some(raw) = self.next_record() {
+            if let Some(problem) = raw.problem {
+                return Err(problem);
+            }
+
+            let record = raw.record;
+            if let Some(first) = rows.first() {
+                if first.len() != record.fields.len() {
+                    return Err(CsvError::RaggedRow {
+                        position: record.position,
+                        expected: first.len(),
+                        found: record.fields.len(),
+                    });
+                }
+            }
+            rows.push(record.fields);
+        }
+        Ok(rows)
+    }
+
+    // keeps going past malformed rows, they are left out of the result and reported as warnings.
+    // rows with a different number of fields to the first good row count as malformed.
+    pub fn parse_lenient(&mut self) -> (Rows<'a>, Vec<Warning>) {
+        let mut rows: Rows<'a> = Vec::new();
+        let mut warnings = Vec::new();
+        let mut row = 0;
+
+        loop {
+            let mark = self.tokenizer.mark();
+            let raw = match self.next_record() {
+                Some(raw) => raw,
+                None => break,
+            };
+            row += 1;
+
+            if let Some(problem) = raw.problem {
+                // a bad quote can swallow every row after it, only give up on the line it started on.
+                let line = self.tokenizer.rewind_to_line_end(mark);
+                warnings.push(Warning {
+                    row,
+                    problem,
+                    raw: line.to_vec(),
+                });
+                continue;
+            }
+
+            let record = raw.record;
+            if let Some(first) = rows.first() {
+                if first.len() != record.fields.len() {
+                    warnings.push(Warning {
+                        row,
+                        problem: CsvError::RaggedRow {
+                            position: record.position,
+                            expected: first.len(),
+                            found: record.fields.len(),
+                        },
+                        raw: raw.raw.to_vec(),
+                    });
+                    continue;
+                }
+            }
+            rows.push(record.fields);
+        }
+
+        (rows, warnings)
+    }
+
+    fn next_record(&mut self) -> Option<RawRecord<'a>> {
+        if self.tokenizer.empty() {
+            return None;
+        }
+
+        let source = self.tokenizer.source;
+        let record_start = self.tokenizer.offset;
+        let position = self.tokenizer.position();
+        // positions are only worked out from these offsets if something turns out to be wrong.
+        let position_of = |offset: usize| {
+            let mut position = position;
+            position.advance_bytes(&source[record_start..offset]);
+            position
+        };
+
+        let mut problem = None;
+        let mut current_row = Vec::with_capacity(self.max_row_width);
+        let mut current_field = FieldBuilder::new();
+        let mut field_start = record_start;
         let mut in_qualifier = false;
+        let mut opening_quote = record_start;
+        // where the quoted part of the current field ends, trimming stops there.
+        let mut quoted_end = None;
+        let mut closing_quote = record_start;
+        let mut at_newline = false;
+        let mut field_end = source.len();
 
-        while let token = self.tokenizer.get_csv_token() {
-            match token {
+        loop {
+            let token_start = self.tokenizer.offset;
+            match self.tokenizer.get_csv_token() {
                 Token::Value(val) => {
-                    for c in val {
-                        current_field.push(*c);
+                    let is_padding = self.dialect.trim && val.iter().all(|c| *c == b' ');
+                    if !in_qualifier && quoted_end.is_some() && !is_padding {
+                        problem.get_or_insert_with(|| CsvError::StrayQuote(position_of(closing_quote)));
                     }
+                    current_field.push_range(source, token_start, self.tokenizer.offset);
                 }
                 Token::Delimiter => {
                     if !in_qualifier {
-                        max_field_size = max_field_size.max(current_field.len());
-                        current_row.push(current_field);
-                        current_field = String::with_capacity(max_field_size);
+                        let field = self.finish_field(current_field, quoted_end.take(), field_start, token_start);
+                        current_row.push(field.unwrap_or_else(|(field, bad)| {
+                            problem.get_or_insert_with(|| CsvError::InvalidUtf8(position_of(bad)));
+                            field
+                        }));
+                        current_field = FieldBuilder::new();
+                        field_start = self.tokenizer.offset;
                     } else {
-                        current_field.push(',');
+                        current_field.push_range(source, token_start, self.tokenizer.offset);
                     }
                 }
                 Token::Newline => {
                     if !in_qualifier {
-                        max_field_size = max_field_size.max(current_field.len());
-                        max_row_width = max_row_width.max(current_row.len());
-                        current_row.push(current_field);
-                        rows.push(current_row);
-                        current_row = Vec::with_capacity(max_row_width);
-                        current_field = String::with_capacity(max_field_size);
-                    } else {
-                        current_field.push('\n');
+                        at_newline = true;
+                        field_end = token_start;
+                        break;
                     }
+                    current_field.push_range(source, token_start, self.tokenizer.offset);
                 }
                 Token::TextQualifier => {
                     in_qualifier = !in_qualifier;
-                }
-    
-                Token::WhiteSpace => {
                     if in_qualifier {
-                        current_field.push(' ');
+                        let is_padding = self.dialect.trim
+                            && current_field.bytes(source).iter().all(|c| *c == b' ');
+                        if quoted_end.is_some() || (!current_field.is_empty() && !is_padding) {
+                            problem.get_or_insert_with(|| CsvError::StrayQuote(position_of(token_start)));
+                        }
+                        if is_padding {
+                            current_field.clear();
+                        }
+                        opening_quote = token_start;
+                    } else {
+                        quoted_end = Some(current_field.len());
+                        closing_quote = token_start;
                     }
                 }
-    
+
                 Token::Escaped => {
-                    if in_qualifier {
-                        current_field.push('"');
-                    }
+                    current_field.push_byte(source, self.tokenizer.quote);
+                }
+
+                Token::Literal(c) => {
+                    current_field.push_byte(source, c);
                 }
                 Token::EndOfFile => {
+                    if in_qualifier {
+                        problem.get_or_insert_with(|| CsvError::UnterminatedQuote(position_of(opening_quote)));
+                    }
                     break;
                 }
             }
         }
-    
-        // Handle the last row if not empty
-        if !current_field.is_empty() {
-            current_row.push(current_field);
-        }
-        if !current_row.is_empty() {
-            rows.push(current_row);
+
+        // a final row without a line break doesn't get a trailing empty field.
+        if at_newline || !current_field.is_empty() || quoted_end.is_some() || current_row.is_empty() {
+            let field = self.finish_field(current_field, quoted_end, field_start, field_end);
+            current_row.push(field.unwrap_or_else(|(field, bad)| {
+                problem.get_or_insert_with(|| CsvError::InvalidUtf8(position_of(bad)));
+                field
+            }));
         }
-    
-        rows
+        self.max_row_width = self.max_row_width.max(current_row.len());
+
+        Some(RawRecord {
+            record: Record {
+                fields: current_row,
+                position,
+            },
+            raw: &source[record_start..self.tokenizer.offset],
+            problem,
+        })
+    }
+
+    // spaces in unquoted fields are kept as is unless the dialect asks for them to be trimmed.
+    // invalid utf-8 is replaced, the error holds the replaced field and roughly where the bad byte was.
+    fn finish_field(
+        &self,
+        field: FieldBuilder,
+        quoted_end: Option<usize>,
+        field_start: usize,
+        field_end: usize,
+    ) -> Result<CsvField<'a>, (CsvField<'a>, usize)> {
+        let source = self.tokenizer.source;
+        let bytes = field.bytes(source);
+        let (start, end) = if !self.dialect.trim {
+            (0, bytes.len())
+        } else {
+            match quoted_end {
+                // only the unquoted tail after the closing quote can be trimmed, "  a  " keeps its spaces.
+                Some(quoted_end) => {
+                    let tail = bytes[quoted_end..].iter().rposition(|c| *c != b' ').map_or(0, |idx| idx + 1);
+                    (0, quoted_end + tail)
+                }
+                None => {
+                    let start = bytes.iter().position(|c| *c != b' ').unwrap_or(bytes.len());
+                    let end = bytes.iter().rposition(|c| *c != b' ').map_or(start, |idx| idx + 1);
+                    (start, end)
+                }
+            }
+        };
+
+        let utf8 = match field.owned {
+            Some(mut owned) => {
+                owned.truncate(end);
+                owned.drain(..start);
+                String::from_utf8(owned)
+                    .map(Cow::Owned)
+                    .map_err(|err| (err.utf8_error(), String::from_utf8_lossy(err.as_bytes()).into_owned()))
+            }
+            None => {
+                let bytes = &source[field.start + start..field.start + end];
+                std::str::from_utf8(bytes)
+                    .map(Cow::Borrowed)
+                    .map_err(|err| (err, String::from_utf8_lossy(bytes).into_owned()))
+            }
+        };
+
+        let field = |text| CsvField {
+            text,
+            raw: Cow::Borrowed(&source[field_start..field_end]),
+            quoted: quoted_end.is_some(),
+        };
+        utf8.map(field)
+            .map_err(|(err, lossy)| (field(Cow::Owned(lossy)), field_start + start + err.valid_up_to()))
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+
+    fn parse(src: &str, dialect: Dialect) -> Rows<'_> {
+        Parser::new(src.as_bytes(), dialect).unwrap().parse()
+    }
+
+    #[test]
+    fn test_default_dialect() {
+        let rows = parse("a,\"b,c\",\"d\"\"e\"\n1,2,3\n", Dialect::default());
+        assert_eq!(rows, vec![vec!["a", "b,c", "d\"e"], vec!["1", "2", "3"]]);
+    }
+
+    #[test]
+    fn test_semicolon_and_tab_delimiters() {
+        let semicolon = Dialect { delimiter: ';', ..Dialect::default() };
+        let rows = parse("a;\"b;c\";d,e\n", semicolon);
+        assert_eq!(rows, vec![vec!["a", "b;c", "d,e"]]);
+
+        let tab = Dialect { delimiter: '\t', ..Dialect::default() };
+        let rows = parse("a\tb\r\nc\td\r\n", tab);
+        assert_eq!(rows, vec![vec!["a", "b"], vec!["c", "d"]]);
+    }
+
+    #[test]
+    fn test_single_quote_and_backslash_escape() {
+        let dialect = Dialect { quote: '\'', escape: Some('\\'), ..Dialect::default() };
+        let rows = parse("'it\\'s',\"plain\",a\\,b\n", dialect);
+        assert_eq!(rows, vec![vec!["it's", "\"plain\"", "a,b"]]);
+    }
+
+    #[test]
+    fn test_line_terminators() {
+        let rows = parse("a,b\rc,d\r", Dialect { line_terminator: LineTerminator::CR, ..Dialect::default() });
+        assert_eq!(rows, vec![vec!["a", "b"], vec!["c", "d"]]);
+
+        // a lone carriage return is data unless the dialect says otherwise.
+        let rows = parse("a\rb,c\n", Dialect::default());
+        assert_eq!(rows, vec![vec!["a\rb", "c"]]);
+    }
+
+    #[test]
+    fn test_whitespace_is_preserved() {
+        let rows = parse("Hello World, a ,\" b \"\n", Dialect::default());
+        assert_eq!(rows, vec![vec!["Hello World", " a ", " b "]]);
+
+        let src = std::fs::read("test_data/formulas.csv").unwrap();
+        let rows = Parser::new(&src, Dialect::default()).unwrap().parse();
+        assert_eq!(rows[2][6], "Hello World");
+    }
+
+    #[test]
+    fn test_trim() {
+        let dialect = Dialect { trim: true, ..Dialect::default() };
+        let rows = parse("  Hello World  , a ,  \" b \"  ,\"c\" d \n", dialect);
+        assert_eq!(rows, vec![vec!["Hello World", "a", " b ", "c d"]]);
+    }
+
+    fn parse_strict(src: &str) -> Result<Rows<'_>, CsvError> {
+        Parser::new(src.as_bytes(), Dialect::default()).unwrap().parse_strict()
+    }
+
+    #[test]
+    fn test_strict_accepts_well_formed_files() {
+        for path in ["test_data/escapes_from_excel.csv", "test_data/formulas.csv", "test_data/medium.csv"] {
+            let src = std::fs::read_to_string(path).unwrap();
+            assert!(parse_strict(&src).is_ok(), "{} should parse", path);
+        }
+
+        let src = std::fs::read_to_string("test_data/escapes_from_excel.csv").unwrap();
+        let rows = parse_strict(&src).unwrap();
+        assert_eq!(rows[0], vec!["commas, and \"quotes\"", "comma, no quote"]);
+        assert_eq!(rows[1], vec![",,,", "\"\"\""]);
+        assert_eq!(rows[2], vec!["\nTest with \nNewline", "End"]);
+
+        let rows = parse_strict("\"\"\"a\"\"\",\"\"\n").unwrap();
+        assert_eq!(rows, vec![vec!["\"a\"", ""]]);
+    }
+
+    #[test]
+    fn test_strict_errors() {
+        let err = parse_strict("a,b\nc,\"d\ne,f\n").unwrap_err();
+        assert!(matches!(err, CsvError::UnterminatedQuote(Position { line: 2, column: 3, byte: 6 })));
+
+        let err = parse_strict("a,b\nc,d\"d\n").unwrap_err();
+        assert!(matches!(err, CsvError::StrayQuote(Position { line: 2, column: 4, byte: 7 })));
+
+        let err = parse_strict("a,\"b\"c\n").unwrap_err();
+        assert!(matches!(err, CsvError::StrayQuote(Position { line: 1, column: 5, .. })));
+
+        let err = parse_strict("a,b\nc,d,e\n").unwrap_err();
+        assert!(matches!(
+            err,
+            CsvError::RaggedRow { position: Position { line: 2, column: 1, byte: 4 }, expected: 2, found: 3 }
+        ));
+
+        // the permissive parser carries on regardless.
+        assert_eq!(parse("a,b\nc,d\"d\n", Dialect::default()).len(), 2);
+    }
+
+    #[test]
+    fn test_lenient_recovery() {
+        let src = b"a,b\nc,d\"d\ne,f\ng,h,i\nj,k\nl,\"m";
+        let (rows, warnings) = Parser::new(src, Dialect::default()).unwrap().parse_lenient();
+        assert_eq!(rows, vec![vec!["a", "b"], vec!["e", "f"], vec!["j", "k"]]);
+
+        assert_eq!(warnings.len(), 3);
+        assert_eq!(warnings[0].row, 2);
+        assert!(matches!(warnings[0].problem, CsvError::StrayQuote(Position { line: 2, column: 4, .. })));
+        assert_eq!(warnings[0].raw, b"c,d\"d\n");
+        assert!(matches!(warnings[1].problem, CsvError::RaggedRow { expected: 2, found: 3, .. }));
+        assert_eq!(warnings[1].raw, b"g,h,i\n");
+        assert_eq!(warnings[2].row, 6);
+        assert!(matches!(warnings[2].problem, CsvError::UnterminatedQuote(Position { line: 6, column: 3, .. })));
+        assert_eq!(warnings[2].raw, b"l,\"m");
+    }
+
+    #[test]
+    fn test_lenient_quarantine() {
+        use crate::excel::Excel;
+
+        let input = temp_path("lenient_input.csv");
+        let quarantine = temp_path("lenient_quarantine.csv");
+        std::fs::write(&input, b"a,b\n1,\"2\n3,4\n5,\xff\n").unwrap();
+
+        let (excel, warnings) =
+            Excel::from_path_lenient(input.to_str().unwrap(), Some(quarantine.to_str().unwrap())).unwrap();
+        assert_eq!(warnings.len(), 2);
+        assert!(matches!(warnings[1].problem, CsvError::InvalidUtf8(Position { line: 4, column: 3, .. })));
+        assert_eq!(excel.rows.len(), 2);
+        assert_eq!(excel.rows[1][0].val.to_string(), "3");
+        // the bad bytes are kept as they were so the row can be fixed up.
+        assert_eq!(std::fs::read(&quarantine).unwrap(), b"1,\"2\n5,\xff\n");
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(quarantine);
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let err = Parser::new(b"a,b\nc,\xff\n", Dialect::default()).unwrap().parse_strict().unwrap_err();
+        assert!(matches!(err, CsvError::InvalidUtf8(Position { line: 2, column: 3, byte: 6 })));
+
+        let rows = Parser::new(b"a,\xff\n", Dialect::default()).unwrap().parse();
+        assert_eq!(rows, vec![vec!["a", "\u{FFFD}"]]);
+    }
+
+    #[test]
+    fn test_invalid_dialect() {
+        for dialect in [
+            Dialect { delimiter: '§', ..Dialect::default() },
+            Dialect { quote: '“', ..Dialect::default() },
+            Dialect { escape: Some('¬'), ..Dialect::default() },
+        ] {
+            assert!(matches!(Parser::new(b"a,b\n", dialect), Err(CsvError::InvalidDialect(_))), "{:?}", dialect);
+        }
+    }
+
+    #[test]
+    fn test_fields_borrow_from_source() {
+        let rows = parse("plain,\"quoted\",\"a\"\"b\",\"two\nlines\"\n", Dialect::default());
+        assert!(matches!(rows[0][0].text, Cow::Borrowed("plain")));
+        assert!(matches!(rows[0][1].text, Cow::Borrowed("quoted")));
+        assert!(matches!(rows[0][3].text, Cow::Borrowed("two\nlines")));
+        // an escaped quote splits the field up, so it has to be copied.
+        assert!(matches!(rows[0][2].text, Cow::Owned(_)));
+        assert_eq!(rows[0][2], "a\"b");
+    }
+
+    #[test]
+    fn test_quote_field() {
+        let dialect = Dialect::default();
+        assert_eq!(dialect.quote_field("plain"), "plain");
+        assert_eq!(dialect.quote_field("a,b"), "\"a,b\"");
+        assert_eq!(dialect.quote_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+
+        let dialect = Dialect { delimiter: ';', quote: '\'', escape: Some('\\'), ..Dialect::default() };
+        assert_eq!(dialect.quote_field("a,b"), "a,b");
+        assert_eq!(dialect.quote_field("a;b"), "'a;b'");
+        assert_eq!(dialect.quote_field("it's"), "'it\\'s'");
+    }
+
+    #[test]
+    fn test_dialect_round_trip() {
+        use crate::excel::{CellAddress, Excel, Grid};
+
+        let dialect = Dialect {
+            delimiter: ';',
+            quote: '\'',
+            escape: Some('\\'),
+            line_terminator: LineTerminator::CRLF,
+            has_header: false,
+            trim: false,
+            decimals: false,
+            quoted_numbers: false,
+            empty_strings: false,
+            quoting: Quoting::Minimal,
+        };
+        let excel = Excel::with_dialect("1;'a;b';it\\'s\r\n2;c;d\r\n".to_string(), dialect).unwrap();
+        let file = temp_path("dialect_round_trip.csv");
+        let path = file.to_str().unwrap();
+        excel.to_file(path).unwrap();
+
+        // unchanged fields are written the way they were read, the escaped quote isn't quoted as well.
+        let written = std::fs::read_to_string(path).unwrap();
+        assert_eq!(written, "1;'a;b';it\\'s\r\n2;c;d\r\n");
+
+        let dialect = Dialect { quoting: Quoting::Never, ..dialect };
+        let excel = Excel { dialect, ..excel };
+        excel.to_file(path).unwrap();
+        let written = std::fs::read_to_string(path).unwrap();
+        // with no quotes the delimiter is escaped instead.
+        assert_eq!(written, "1;a\\;b;it\\'s\r\n2;c;d\r\n");
+
+        let reread = Excel::from_path_with_dialect(path, dialect).unwrap();
+        let (start, end) = (CellAddress { row: 0, col: 0 }, CellAddress { row: 1, col: 2 });
+        assert_eq!(reread.range(start, end), excel.range(start, end));
+        let _ = std::fs::remove_file(file);
+    }
+}
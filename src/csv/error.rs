@@ -0,0 +1,109 @@
+use std::fmt;
+use std::io;
+
+// where something is in the source, line and column are 1 based, byte is a 0 based offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub byte: usize,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self {
+            line: 1,
+            column: 1,
+            byte: 0,
+        }
+    }
+}
+
+impl Position {
+    // the position after `bytes`, which don't have to be valid utf-8. columns count characters
+    // by skipping utf-8 continuation bytes.
+    pub(crate) fn advance_bytes(&mut self, bytes: &[u8]) {
+        for (idx, byte) in bytes.iter().enumerate() {
+            self.byte += 1;
+            if *byte == b'\n' || (*byte == b'\r' && bytes.get(idx + 1) != Some(&b'\n')) {
+                self.line += 1;
+                self.column = 1;
+            } else if byte & 0xC0 != 0x80 {
+                self.column += 1;
+            }
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {} (byte {})", self.line, self.column, self.byte)
+    }
+}
+
+#[derive(Debug)]
+pub enum CsvError {
+    UnterminatedQuote(Position), // the position of the opening quote.
+    StrayQuote(Position), // a quote in the middle of an unquoted field or text right after a closing quote.
+    RaggedRow {
+        position: Position, // the start of the offending row.
+        expected: usize,
+        found: usize,
+    },
+    InvalidUtf8(Position),
+    RecordTooLarge {
+        position: Position, // the start of the record.
+        limit: usize,
+    },
+    InvalidDialect(char), // a delimiter, quote or escape character that isn't ascii.
+    Io(io::Error),
+}
+
+impl CsvError {
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            CsvError::UnterminatedQuote(position) => Some(*position),
+            CsvError::StrayQuote(position) => Some(*position),
+            CsvError::RaggedRow { position, .. } => Some(*position),
+            CsvError::InvalidUtf8(position) => Some(*position),
+            CsvError::RecordTooLarge { position, .. } => Some(*position),
+            CsvError::InvalidDialect(_) | CsvError::Io(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvError::UnterminatedQuote(position) => write!(f, "Unterminated quote starting at {}", position),
+            CsvError::StrayQuote(position) => write!(f, "Stray quote at {}", position),
+            CsvError::RaggedRow { position, expected, found } => {
+                write!(f, "Expected {} fields but found {} in the row at {}", expected, found, position)
+            }
+            CsvError::InvalidUtf8(position) => write!(f, "Invalid UTF-8 at {}", position),
+            CsvError::RecordTooLarge { position, limit } => {
+                write!(f, "The record at {} is longer than {} bytes", position, limit)
+            }
+            CsvError::InvalidDialect(c) => write!(f, "Delimiter, quote and escape characters have to be ascii, got {:?}", c),
+            CsvError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+impl From<io::Error> for CsvError {
+    fn from(err: io::Error) -> Self {
+        CsvError::Io(err)
+    }
+}
+
+// for functions that can only fail to read, like ones reading in a sniffed dialect.
+impl From<CsvError> for io::Error {
+    fn from(err: CsvError) -> Self {
+        match err {
+            CsvError::Io(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::thread;
+use super::{ascii_byte, CsvError, Dialect, LineTerminator, Parser, Rows};
+
+// inputs smaller than this aren't worth starting threads for.
+pub const MIN_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+// parses big inputs a chunk per thread, the rows come back in the same order and are exactly what
+// Parser::parse would have returned for the whole input.
+pub fn parse_parallel(src: &[u8], dialect: Dialect) -> Result<Rows<'_>, CsvError> {
+    Ok(map_chunks(src, dialect, |rows| rows)?.into_iter().flatten().collect())
+}
+
+// parses each chunk on its own thread and hands the rows to `f` while still on that thread, so work
+// done on the rows afterwards is split up too. the results are in the order of the chunks.
+pub fn map_chunks<'a, T, F>(src: &'a [u8], dialect: Dialect, f: F) -> Result<Vec<T>, CsvError>
+where
+    T: Send,
+    F: Fn(Rows<'a>) -> T + Sync,
+{
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let chunks = split_records(src, dialect, threads.min(src.len() / MIN_CHUNK_SIZE).max(1))?;
+    if chunks.len() == 1 {
+        return Ok(vec![f(Parser::new(src, dialect)?.parse())]);
+    }
+
+    let f = &f;
+    thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| scope.spawn(move || Ok(f(Parser::new(chunk, dialect)?.parse()))))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+// cuts the source into roughly `chunks` even pieces, each ending on a line break that isn't inside
+// of a quoted field. whether a line break is quoted comes down to how many quotes are before it,
+// counted the same way the tokenizer does: the byte after an escape character never counts.
+pub fn split_records(src: &[u8], dialect: Dialect, chunks: usize) -> Result<Vec<&[u8]>, CsvError> {
+    let quote = ascii_byte(dialect.quote)?;
+    let escape = dialect.escape.map(ascii_byte).transpose()?;
+    let cr_terminator = dialect.line_terminator == LineTerminator::CR;
+
+    let mut pieces = Vec::with_capacity(chunks);
+    let mut start = 0;
+    let mut offset = 0;
+    let mut in_qualifier = false;
+    for chunk in 1..chunks {
+        let target = src.len() * chunk / chunks;
+        if target <= offset {
+            continue;
+        }
+
+        // most of the way there only the quotes matter, which can be counted a lot faster.
+        match escape {
+            None => {
+                let quotes = src[offset..target].iter().filter(|byte| **byte == quote).count();
+                in_qualifier ^= quotes % 2 == 1;
+                offset = target;
+            }
+            Some(escape) => {
+                while offset < target {
+                    if src[offset] == escape {
+                        offset += 1;
+                    } else if src[offset] == quote {
+                        in_qualifier = !in_qualifier;
+                    }
+                    offset += 1;
+                }
+            }
+        }
+
+        // then on to the first line break outside of quotes.
+        while offset < src.len() {
+            let byte = src[offset];
+            offset += 1;
+            if Some(byte) == escape {
+                offset += 1;
+            } else if byte == quote {
+                in_qualifier = !in_qualifier;
+            } else if !in_qualifier && is_line_end(src, offset - 1, cr_terminator) {
+                break;
+            }
+        }
+
+        let end = offset.min(src.len());
+        if end > start && end < src.len() {
+            pieces.push(&src[start..end]);
+            start = end;
+        }
+        offset = end;
+    }
+
+    pieces.push(&src[start..]);
+    Ok(pieces)
+}
+
+// '\n' always ends a line, in a '\r' dialect so does a '\r' that isn't the start of a "\r\n".
+fn is_line_end(src: &[u8], idx: usize, cr_terminator: bool) -> bool {
+    match src[idx] {
+        b'\n' => true,
+        b'\r' => cr_terminator && src.get(idx + 1) != Some(&b'\n'),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_chunks(src: &[u8], dialect: Dialect, chunks: usize) -> Rows<'_> {
+        split_records(src, dialect, chunks)
+            .unwrap()
+            .into_iter()
+            .flat_map(|chunk| Parser::new(chunk, dialect).unwrap().parse())
+            .collect()
+    }
+
+    #[test]
+    fn test_chunks_match_parser() {
+        let excel = std::fs::read("test_data/escapes_from_excel.csv").unwrap();
+        let medium = std::fs::read("test_data/medium.csv").unwrap();
+        let mut quoted_lines = Vec::new();
+        for idx in 0..200 {
+            quoted_lines.extend_from_slice(format!("{},\"line\nbreak, \"\"{}\"\"\n\",end\n", idx, idx).as_bytes());
+        }
+
+        for src in [&excel, &medium, &quoted_lines] {
+            let expected = Parser::new(src, Dialect::default()).unwrap().parse();
+            for chunks in [1, 2, 3, 7, 64] {
+                assert_eq!(parse_chunks(src, Dialect::default(), chunks), expected, "{} chunks", chunks);
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunks_never_split_quotes() {
+        let src = b"a,\"1\n2\n3\n4\n5\n6\n7\n8\",b\nc,d\n";
+        let chunks = split_records(src, Dialect::default(), 4).unwrap();
+        assert_eq!(chunks, vec![&b"a,\"1\n2\n3\n4\n5\n6\n7\n8\",b\n"[..], b"c,d\n"]);
+
+        let dialect = Dialect { quote: '\'', escape: Some('\\'), line_terminator: LineTerminator::CR, ..Dialect::default() };
+        let src = b"'a\\'\rb',c\rd,e\r\nf,g\r";
+        let chunks = split_records(src, dialect, 3).unwrap();
+        assert_eq!(chunks, vec![&b"'a\\'\rb',c\r"[..], b"d,e\r\n", b"f,g\r"]);
+        assert_eq!(parse_chunks(src, dialect, 3), Parser::new(src, dialect).unwrap().parse());
+    }
+
+    #[test]
+    fn test_non_ascii_quote() {
+        let dialect = Dialect { quote: '“', ..Dialect::default() };
+        assert!(matches!(split_records(b"a,b\n", dialect, 2), Err(CsvError::InvalidDialect('“'))));
+        assert!(matches!(parse_parallel(b"a,b\n", dialect), Err(CsvError::InvalidDialect('“'))));
+    }
+
+    #[test]
+    fn test_parse_parallel() {
+        let src = std::fs::read("test_data/medium.csv").unwrap();
+        assert_eq!(parse_parallel(&src, Dialect::default()).unwrap(), Parser::new(&src, Dialect::default()).unwrap().parse());
+    }
+}
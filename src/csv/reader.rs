@@ -0,0 +1,234 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use super::{ascii_byte, CsvError, Dialect, LineTerminator, Parser, Position, Record};
+
+// reads records one at a time from any buffered source so the whole file never has to be in memory.
+// a record is read line by line until its quotes are balanced, so a quoted field can span any
+// number of lines and buffer refills. a dialect with characters that aren't ascii is an error the
+// first time a record is asked for.
+//
+// malformed records come back as errors and reading carries on with the next line, rows with an
+// unexpected number of fields are not checked for since there's no first row to compare against
+// until it has been read. a record longer than the limit is an error that stops the reading, it's
+// most likely an unterminated quote that would otherwise be read to the end of the source.
+pub struct Reader<R: BufRead> {
+    source: R,
+    dialect: Dialect,
+    max_record_size: usize,
+    buffer: Vec<u8>,
+    position: Position, // where the next unread byte is in the whole stream.
+    carry: Vec<u8>, // lines already read from the source that still need to be parsed.
+    carry_start: usize, // how much of carry has been read back out, it's only cleared once it all has.
+    pending: VecDeque<Result<Record<'static>, CsvError>>,
+    done: bool,
+}
+
+impl Reader<BufReader<File>> {
+    pub fn from_path(path: &str, dialect: Dialect) -> std::io::Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?), dialect))
+    }
+}
+
+// records can be this many bytes long before they're an error, unless the reader is given another limit.
+pub const MAX_RECORD_SIZE: usize = 64 << 20;
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(source: R, dialect: Dialect) -> Self {
+        Self {
+            source,
+            dialect,
+            max_record_size: MAX_RECORD_SIZE,
+            buffer: Vec::new(),
+            position: Position::default(),
+            carry: Vec::new(),
+            carry_start: 0,
+            pending: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    pub fn max_record_size(mut self, bytes: usize) -> Self {
+        self.max_record_size = bytes;
+        self
+    }
+
+    // fills the buffer with the next record's worth of lines, false once the source is exhausted.
+    fn fill_record(&mut self) -> Result<bool, CsvError> {
+        let line_break = self.line_break();
+        ascii_byte(self.dialect.delimiter)?;
+        let quote = ascii_byte(self.dialect.quote)?;
+        let escape = self.dialect.escape.map(ascii_byte).transpose()?;
+
+        self.buffer.clear();
+        let mut in_qualifier = false;
+        let mut escaped = false;
+        loop {
+            let start = self.buffer.len();
+            if self.read_line(line_break)? == 0 {
+                break;
+            }
+            if self.buffer.len() > self.max_record_size {
+                return Err(CsvError::RecordTooLarge {
+                    position: self.position,
+                    limit: self.max_record_size,
+                });
+            }
+
+            for &byte in &self.buffer[start..] {
+                if escaped {
+                    escaped = false;
+                } else if Some(byte) == escape {
+                    escaped = true;
+                } else if byte == quote {
+                    in_qualifier = !in_qualifier;
+                }
+            }
+
+            if !in_qualifier {
+                break;
+            }
+        }
+
+        Ok(!self.buffer.is_empty())
+    }
+
+    fn line_break(&self) -> u8 {
+        match self.dialect.line_terminator {
+            LineTerminator::CR => b'\r',
+            _ => b'\n',
+        }
+    }
+
+    fn read_line(&mut self, line_break: u8) -> std::io::Result<usize> {
+        if self.carry_start == self.carry.len() {
+            self.carry.clear();
+            self.carry_start = 0;
+            // no more than one byte past the limit, so a line with no end isn't read in whole.
+            let limit = (self.max_record_size + 1).saturating_sub(self.buffer.len());
+            return (&mut self.source).take(limit as u64).read_until(line_break, &mut self.buffer);
+        }
+
+        // read from an offset rather than draining the front, which would move the rest every line.
+        let rest = &self.carry[self.carry_start..];
+        let len = rest.iter().position(|byte| *byte == line_break).map_or(rest.len(), |idx| idx + 1);
+        self.buffer.extend_from_slice(&rest[..len]);
+        self.carry_start += len;
+        Ok(len)
+    }
+
+    fn parse_buffer(&mut self) -> Result<(), CsvError> {
+        let mut parser = Parser::new(&self.buffer, self.dialect)?;
+        parser.tokenizer.origin = self.position;
+        loop {
+            let mark = parser.tokenizer.mark();
+            let raw = match parser.next_record() {
+                Some(raw) => raw,
+                None => break,
+            };
+
+            match raw.problem {
+                None => self.pending.push_back(Ok(Record {
+                    fields: raw.record.fields.into_iter().map(|field| field.into_owned()).collect(),
+                    position: raw.record.position,
+                })),
+                Some(problem) => {
+                    self.pending.push_back(Err(problem));
+                    // same as lenient parsing, a bad quote may have swallowed the lines after it.
+                    parser.tokenizer.rewind_to_line_end(mark);
+                    let mut carry = self.buffer[parser.tokenizer.offset..].to_vec();
+                    carry.extend_from_slice(&self.carry[self.carry_start..]);
+                    self.carry = carry;
+                    self.carry_start = 0;
+                    break;
+                }
+            }
+        }
+        self.position = parser.tokenizer.position();
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = Result<Record<'static>, CsvError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() && !self.done {
+            match self.fill_record().and_then(|filled| filled.then(|| self.parse_buffer()).transpose()) {
+                Ok(Some(())) => {}
+                Ok(None) => self.done = true,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        self.pending.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::csv::Rows;
+
+    #[test]
+    fn test_reader_matches_parser() {
+        let src = std::fs::read_to_string("test_data/escapes_from_excel.csv").unwrap();
+        let expected = Parser::new(src.as_bytes(), Dialect::default()).unwrap().parse();
+
+        // a tiny buffer forces the quoted newline field to span several refills.
+        let source = BufReader::with_capacity(4, Cursor::new(src.as_bytes()));
+        let rows: Rows = Reader::new(source, Dialect::default())
+            .map(|record| record.unwrap().fields)
+            .collect();
+        assert_eq!(rows, expected);
+    }
+
+    #[test]
+    fn test_reader_positions_and_errors() {
+        let source = Cursor::new(b"a,b\nc,\"d\ne\",f\ng,\xff\nh\"i,j\nk,l".to_vec());
+        let records: Vec<Result<Record, CsvError>> = Reader::new(source, Dialect::default()).collect();
+        assert_eq!(records.len(), 5);
+
+        let record = records[1].as_ref().unwrap();
+        assert_eq!(record.fields, vec!["c", "d\ne", "f"]);
+        assert_eq!(record.position, Position { line: 2, column: 1, byte: 4 });
+
+        assert!(matches!(records[2], Err(CsvError::InvalidUtf8(Position { line: 4, column: 3, byte: 16 }))));
+        assert!(matches!(records[3], Err(CsvError::StrayQuote(Position { line: 5, column: 2, .. }))));
+
+        let record = records[4].as_ref().unwrap();
+        assert_eq!(record.fields, vec!["k", "l"]);
+        assert_eq!(record.position.line, 6);
+    }
+
+    #[test]
+    fn test_unterminated_quote_hits_the_limit() {
+        // the quote on the first line would swallow everything after it, it's stopped once the
+        // record gets longer than the limit rather than being read to the end.
+        let mut src = b"\"a,b\n".to_vec();
+        for line in 0..20_000 {
+            src.extend_from_slice(format!("{},x\n", line).as_bytes());
+        }
+        let reader = Reader::new(Cursor::new(src), Dialect::default()).max_record_size(1024);
+        let records: Vec<Result<Record, CsvError>> = reader.collect();
+        assert_eq!(records.len(), 1);
+        assert!(matches!(
+            records[0],
+            Err(CsvError::RecordTooLarge { position: Position { line: 1, column: 1, byte: 0 }, limit: 1024 })
+        ));
+
+        // and so is a line that never ends.
+        let src = Cursor::new(vec![b'a'; 5000]);
+        let records: Vec<Result<Record, CsvError>> = Reader::new(src, Dialect::default()).max_record_size(1024).collect();
+        assert!(matches!(records[..], [Err(CsvError::RecordTooLarge { .. })]));
+
+        // records under the limit are read as before.
+        let src = Cursor::new(b"\"a\nb\",c\nd,e\n".to_vec());
+        let records: Vec<Result<Record, CsvError>> = Reader::new(src, Dialect::default()).max_record_size(8).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].as_ref().unwrap().fields, vec!["d", "e"]);
+    }
+}
@@ -0,0 +1,192 @@
+// finds the next byte that could end an unquoted value (the delimiter, quote, escape, '\n' or '\r')
+// several bytes at a time. x86_64 compares 32 or 16 bytes at once depending on whether the cpu has
+// avx2, anything else checks a u64 word at a time, and whatever is left at the end is done a byte at
+// a time with a lookup table.
+pub struct Specials {
+    bytes: [u8; 5],
+    table: [bool; 256],
+    method: Method,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Method {
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+    #[cfg(target_arch = "x86_64")]
+    Sse2,
+    // still built on x86_64 so the tests can check it against the others.
+    #[cfg_attr(target_arch = "x86_64", allow(dead_code))]
+    Swar,
+}
+
+impl Specials {
+    // the same byte can show up more than once, e.g. when there's no escape character it's the quote again.
+    pub fn new(bytes: [u8; 5]) -> Self {
+        let mut table = [false; 256];
+        for byte in bytes {
+            table[byte as usize] = true;
+        }
+
+        Self {
+            bytes,
+            table,
+            method: Method::detect(),
+        }
+    }
+
+    pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+        match self.method {
+            // the cpu was checked for avx2 before picking it and every x86_64 cpu has sse2.
+            #[cfg(target_arch = "x86_64")]
+            Method::Avx2 => unsafe { self.find_avx2(haystack) },
+            #[cfg(target_arch = "x86_64")]
+            Method::Sse2 => unsafe { self.find_sse2(haystack) },
+            Method::Swar => self.find_swar(haystack),
+        }
+    }
+
+    fn find_scalar(&self, haystack: &[u8]) -> Option<usize> {
+        haystack.iter().position(|byte| self.table[*byte as usize])
+    }
+
+    // classic "has a zero byte" trick: xor-ing with the needle turns matching bytes into zeros. the
+    // trick can flag bytes after a real zero, but never before one, so the lowest flagged byte is right.
+    fn find_swar(&self, haystack: &[u8]) -> Option<usize> {
+        const LOW: u64 = 0x0101_0101_0101_0101;
+        const HIGH: u64 = 0x8080_8080_8080_8080;
+        let needles = self.bytes.map(|byte| LOW * byte as u64);
+
+        let mut chunks = haystack.chunks_exact(8);
+        for (idx, chunk) in chunks.by_ref().enumerate() {
+            let word = u64::from_le_bytes(chunk.try_into().unwrap());
+            let mut hits = 0;
+            for needle in needles {
+                let x = word ^ needle;
+                hits |= x.wrapping_sub(LOW) & !x & HIGH;
+            }
+            if hits != 0 {
+                return Some(idx * 8 + hits.trailing_zeros() as usize / 8);
+            }
+        }
+
+        let done = haystack.len() - chunks.remainder().len();
+        self.find_scalar(chunks.remainder()).map(|idx| done + idx)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "sse2")]
+    unsafe fn find_sse2(&self, haystack: &[u8]) -> Option<usize> {
+        use std::arch::x86_64::*;
+
+        let needles = self.bytes.map(|byte| _mm_set1_epi8(byte as i8));
+        let mut offset = 0;
+        while offset + 16 <= haystack.len() {
+            // the loop condition keeps the 16 byte load inside of the haystack.
+            let chunk = _mm_loadu_si128(haystack.as_ptr().add(offset) as *const __m128i);
+            let mut hits = _mm_setzero_si128();
+            for needle in needles {
+                hits = _mm_or_si128(hits, _mm_cmpeq_epi8(chunk, needle));
+            }
+            let mask = _mm_movemask_epi8(hits) as u32;
+            if mask != 0 {
+                return Some(offset + mask.trailing_zeros() as usize);
+            }
+            offset += 16;
+        }
+
+        self.find_scalar(&haystack[offset..]).map(|idx| offset + idx)
+    }
+
+    // only safe to call once is_x86_feature_detected!("avx2") has said yes.
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    unsafe fn find_avx2(&self, haystack: &[u8]) -> Option<usize> {
+        use std::arch::x86_64::*;
+
+        let needles = self.bytes.map(|byte| _mm256_set1_epi8(byte as i8));
+        let mut offset = 0;
+        while offset + 32 <= haystack.len() {
+            let chunk = _mm256_loadu_si256(haystack.as_ptr().add(offset) as *const __m256i);
+            let mut hits = _mm256_setzero_si256();
+            for needle in needles {
+                hits = _mm256_or_si256(hits, _mm256_cmpeq_epi8(chunk, needle));
+            }
+            let mask = _mm256_movemask_epi8(hits) as u32;
+            if mask != 0 {
+                return Some(offset + mask.trailing_zeros() as usize);
+            }
+            offset += 32;
+        }
+
+        self.find_sse2(&haystack[offset..]).map(|idx| offset + idx)
+    }
+}
+
+impl Method {
+    #[cfg(target_arch = "x86_64")]
+    fn detect() -> Self {
+        // sse2 is part of x86_64 itself, avx2 has to be asked about.
+        if is_x86_feature_detected!("avx2") {
+            Method::Avx2
+        } else {
+            Method::Sse2
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    fn detect() -> Self {
+        Method::Swar
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn methods() -> Vec<Method> {
+        let mut methods = vec![Method::Swar];
+        #[cfg(target_arch = "x86_64")]
+        {
+            methods.push(Method::Sse2);
+            if is_x86_feature_detected!("avx2") {
+                methods.push(Method::Avx2);
+            }
+        }
+        methods
+    }
+
+    #[test]
+    fn test_every_method_finds_the_first_special() {
+        let mut specials = Specials::new([b',', b'"', b'"', b'\n', b'\r']);
+        let filler = "abcdefghij klmnopqrstuvwxyz 0123456789 é ü ~".as_bytes();
+
+        for method in methods() {
+            specials.method = method;
+            // every length either side of the 8/16/32 byte steps, with the special byte at every spot.
+            for len in 0..100 {
+                let mut haystack: Vec<u8> = filler.iter().cycle().take(len).copied().collect();
+                assert_eq!(specials.find(&haystack), None, "{:?} len {}", method, len);
+
+                for at in 0..len {
+                    for special in [b',', b'"', b'\n', b'\r'] {
+                        let old = haystack[at];
+                        haystack[at] = special;
+                        assert_eq!(specials.find(&haystack), Some(at), "{:?} len {} at {}", method, len, at);
+                        haystack[at] = old;
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_high_bytes_are_not_specials() {
+        // 0x80 and up are where the swar trick could go wrong if it compared signed bytes.
+        let specials = Specials::new([b';', b'\'', b'\\', b'\n', b'\r']);
+        let haystack: Vec<u8> = (0x80..=0xFF).chain([b'\\']).collect();
+        for method in methods() {
+            let specials = Specials { method, ..Specials::new(specials.bytes) };
+            assert_eq!(specials.find(&haystack), Some(128), "{:?}", method);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use super::{Dialect, LineTerminator, Parser, Rows};
+
+// how much of the input is looked at when guessing the dialect.
+pub const SAMPLE_SIZE: usize = 64 * 1024;
+
+const DELIMITERS: [char; 4] = [',', ';', '\t', '|'];
+const QUOTES: [char; 2] = ['"', '\''];
+
+// guesses the dialect of a csv file from the start of its contents. anything that can't be
+// worked out from the sample falls back to the default dialect.
+pub fn sniff(src: &[u8]) -> Dialect {
+    let sample = take_sample(src);
+    let text = String::from_utf8_lossy(sample);
+    let line_terminator = sniff_line_terminator(&text);
+    let quote = sniff_quote(&text);
+
+    let mut best: Option<(Dialect, f64, usize)> = None;
+    for delimiter in DELIMITERS {
+        let dialect = Dialect {
+            delimiter,
+            quote,
+            line_terminator,
+            ..Dialect::default()
+        };
+
+        if let Some((consistency, width)) = score_delimiter(sample, dialect) {
+            let better = match best {
+                Some((_, best_consistency, best_width)) => {
+                    consistency > best_consistency || (consistency == best_consistency && width > best_width)
+                }
+                None => true,
+            };
+            if better {
+                best = Some((dialect, consistency, width));
+            }
+        }
+    }
+
+    let mut dialect = match best {
+        Some((dialect, _, _)) => dialect,
+        None => Dialect {
+            quote,
+            line_terminator,
+            ..Dialect::default()
+        },
+    };
+    dialect.has_header = sniff_header(sample, dialect);
+    dialect
+}
+
+// the first SAMPLE_SIZE bytes, cut back to the last full line if the input is longer than that.
+fn take_sample(src: &[u8]) -> &[u8] {
+    if src.len() <= SAMPLE_SIZE {
+        return src;
+    }
+
+    let sample = &src[..SAMPLE_SIZE];
+    match sample.iter().rposition(|byte| *byte == b'\n' || *byte == b'\r') {
+        Some(idx) => &sample[..idx + 1],
+        None => sample,
+    }
+}
+
+fn sniff_line_terminator(sample: &str) -> LineTerminator {
+    let (mut lf, mut crlf, mut cr) = (0, 0, 0);
+    let mut chars = sample.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' if chars.peek() == Some(&'\n') => {
+                chars.next();
+                crlf += 1;
+            }
+            '\r' => cr += 1,
+            '\n' => lf += 1,
+            _ => {}
+        }
+    }
+
+    if crlf >= lf && crlf >= cr && crlf > 0 {
+        LineTerminator::CRLF
+    } else if cr > lf {
+        LineTerminator::CR
+    } else {
+        LineTerminator::LF
+    }
+}
+
+// counts quotes that open or close a field, i.e. ones next to a possible delimiter or a line break.
+fn sniff_quote(sample: &str) -> char {
+    let chars: Vec<char> = sample.chars().collect();
+    let is_boundary = |c: Option<&char>| match c {
+        None => true,
+        Some(c) => DELIMITERS.contains(c) || *c == '\n' || *c == '\r',
+    };
+
+    let mut best = ('"', 0);
+    for quote in QUOTES {
+        let mut count = 0;
+        for (idx, c) in chars.iter().enumerate() {
+            if *c != quote {
+                continue;
+            }
+            let before = if idx == 0 { None } else { chars.get(idx - 1) };
+            if is_boundary(before) || is_boundary(chars.get(idx + 1)) {
+                count += 1;
+            }
+        }
+        if count > best.1 {
+            best = (quote, count);
+        }
+    }
+    best.0
+}
+
+// parses the sample with the delimiter and returns how many rows share the most common width and
+// that width. a delimiter that never splits a row isn't a candidate at all.
+fn score_delimiter(sample: &[u8], dialect: Dialect) -> Option<(f64, usize)> {
+    let rows = parse_sample(sample, dialect);
+    if rows.is_empty() {
+        return None;
+    }
+
+    let mut widths: Vec<(usize, usize)> = Vec::new();
+    for row in &rows {
+        match widths.iter_mut().find(|(width, _)| *width == row.len()) {
+            Some((_, count)) => *count += 1,
+            None => widths.push((row.len(), 1)),
+        }
+    }
+
+    let (width, count) = widths
+        .into_iter()
+        .max_by_key(|(width, count)| (*count, *width))?;
+    if width < 2 {
+        return None;
+    }
+
+    Some((count as f64 / rows.len() as f64, width))
+}
+
+// votes column by column on whether the first row looks different to the rest, the same way
+// python's csv.Sniffer does: a text cell on top of numbers or a cell of the wrong length is a header.
+fn sniff_header(sample: &[u8], dialect: Dialect) -> bool {
+    let rows = parse_sample(sample, dialect);
+    if rows.len() < 2 {
+        return false;
+    }
+
+    let header = &rows[0];
+    let mut votes = 0;
+    for (col, title) in header.iter().enumerate() {
+        let values: Vec<&str> = rows[1..]
+            .iter()
+            .filter_map(|row| row.get(col))
+            .map(|value| value.text.as_ref())
+            .filter(|value| !value.is_empty())
+            .collect();
+        if values.is_empty() {
+            continue;
+        }
+
+        if values.iter().all(|value| is_number(value)) {
+            votes += if is_number(title) { -1 } else { 1 };
+        } else {
+            let length = values[0].chars().count();
+            if values.iter().all(|value| value.chars().count() == length) {
+                votes += if title.chars().count() == length { -1 } else { 1 };
+            }
+        }
+    }
+
+    votes > 0
+}
+
+fn is_number(value: &str) -> bool {
+    value.trim().parse::<f64>().is_ok()
+}
+
+fn parse_sample(sample: &[u8], dialect: Dialect) -> Rows<'_> {
+    // the dialects tried are all ascii, so there's always a parser.
+    Parser::new(sample, dialect)
+        .map(|mut parser| parser.parse())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|row| !(row.len() == 1 && row[0].is_empty()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_delimiters() {
+        assert_eq!(sniff(b"a,b,c\n1,2,3\n").delimiter, ',');
+        assert_eq!(sniff(b"a;b;c\n1,5;2,5;3\n").delimiter, ';');
+        assert_eq!(sniff(b"a\tb\tc\n1\t2\t3\n").delimiter, '\t');
+        assert_eq!(sniff(b"a|b, c|d\n1|2|3\n").delimiter, '|');
+
+        // a single column has nothing to split on, stick with the default.
+        assert_eq!(sniff(b"a\nb\nc\n").delimiter, ',');
+    }
+
+    #[test]
+    fn test_sniff_quote_and_line_terminator() {
+        let dialect = sniff(b"'a;b';c\r\n'd';e\r\n");
+        assert_eq!(dialect.quote, '\'');
+        assert_eq!(dialect.delimiter, ';');
+        assert_eq!(dialect.line_terminator, LineTerminator::CRLF);
+
+        let dialect = sniff(b"\"it's\",b\n\"c\",d\n");
+        assert_eq!(dialect.quote, '"');
+        assert_eq!(dialect.line_terminator, LineTerminator::LF);
+    }
+
+    #[test]
+    fn test_sniff_header() {
+        assert!(sniff(b"id,name,price\n1,apple,0.5\n2,pear,0.75\n").has_header);
+        assert!(!sniff(b"123,Hello,3.14\n456,World,2.71\n").has_header);
+
+        let medium = std::fs::read("test_data/medium.csv").unwrap();
+        let dialect = sniff(&medium);
+        assert_eq!(dialect.delimiter, ',');
+        assert!(dialect.has_header);
+    }
+}
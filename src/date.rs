@@ -0,0 +1,127 @@
+use std::fmt;
+
+// a calendar date with no time or timezone, written out as yyyy-mm-dd.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    year: i32,
+    month: u8,
+    day: u8,
+}
+
+impl Date {
+    // None unless the day exists, so 2023-02-29 isn't a date.
+    pub fn new(year: i32, month: u8, day: u8) -> Option<Self> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+        Some(Self { year, month, day })
+    }
+
+    pub fn year(&self) -> i32 {
+        self.year
+    }
+
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    // reads a date written in `format`, where %Y is a four digit year, %m a month, %d a day and
+    // anything else has to match exactly. "%d/%m/%Y" reads "19/10/2026". months and days can be one
+    // or two digits.
+    pub fn parse(text: &str, format: &str) -> Option<Self> {
+        let mut text = text.as_bytes();
+        let mut format = format.as_bytes();
+        let (mut year, mut month, mut day) = (None, None, None);
+
+        while let Some((&c, rest)) = format.split_first() {
+            if c == b'%' {
+                let (&spec, rest) = rest.split_first()?;
+                format = rest;
+                let (min, max) = if spec == b'Y' { (4, 4) } else { (1, 2) };
+                let len = text.iter().take(max).take_while(|c| c.is_ascii_digit()).count();
+                if len < min {
+                    return None;
+                }
+                let (digits, rest) = text.split_at(len);
+                text = rest;
+                let number = std::str::from_utf8(digits).ok()?.parse::<u32>().ok()?;
+                match spec {
+                    b'Y' => year = Some(number as i32),
+                    b'm' => month = Some(number as u8),
+                    b'd' => day = Some(number as u8),
+                    _ => return None,
+                }
+            } else {
+                text = text.strip_prefix(&[c])?;
+                format = rest;
+            }
+        }
+
+        if !text.is_empty() {
+            return None;
+        }
+        Self::new(year?, month?, day?)
+    }
+
+    // the date `days` after 1970-01-01, before it when negative.
+    pub fn from_days(days: i64) -> Option<Self> {
+        // counted in 400 year eras starting on the 1st of march, so leap days fall at the end of a year.
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month + 2) / 5 + 1;
+        let month = if month < 10 { month + 3 } else { month - 9 };
+        let year = year_of_era + era * 400 + (month <= 2) as i64;
+        Self::new(i32::try_from(year).ok()?, month as u8, day as u8)
+    }
+}
+
+fn days_in_month(year: i32, month: u8) -> u8 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let date = Date::new(2026, 10, 19).unwrap();
+        assert_eq!(Date::parse("2026-10-19", "%Y-%m-%d"), Some(date));
+        assert_eq!(Date::parse("19/10/2026", "%d/%m/%Y"), Some(date));
+        assert_eq!(Date::parse("10/19/2026", "%m/%d/%Y"), Some(date));
+        assert_eq!(Date::parse("1/2/2024", "%d/%m/%Y"), Date::new(2024, 2, 1));
+        assert_eq!(date.to_string(), "2026-10-19");
+
+        assert_eq!(Date::parse("2024-02-29", "%Y-%m-%d"), Date::new(2024, 2, 29));
+        for text in ["2023-02-29", "2026-13-01", "2026-10-19x", "26-10-19", "2026/10/19", ""] {
+            assert_eq!(Date::parse(text, "%Y-%m-%d"), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_from_days() {
+        assert_eq!(Date::from_days(0), Date::new(1970, 1, 1));
+        assert_eq!(Date::from_days(-1), Date::new(1969, 12, 31));
+        assert_eq!(Date::from_days(19_782), Date::new(2024, 2, 29));
+        assert_eq!(Date::from_days(20_745), Date::new(2026, 10, 19));
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+use std::str::FromStr;
+
+// a base 10 number for columns like money, kept exactly as written instead of rounded to the nearest
+// float. trailing zeros are kept too, so "-35.70" prints back as "-35.70" and not "-35.7". there's no
+// arithmetic on decimals, sums and formulas work on to_f64. up to 19 significant digits, anything
+// longer doesn't parse and is left as a float. zero has no sign, "-0.00" is the same as "0.00".
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    digits: u64,
+    scale: u16, // how many of the digits are after the decimal point.
+    negative: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDecimalError;
+
+impl fmt::Display for ParseDecimalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid decimal")
+    }
+}
+
+impl std::error::Error for ParseDecimalError {}
+
+impl Decimal {
+    pub fn new(digits: u64, scale: u16, negative: bool) -> Self {
+        Self {
+            digits,
+            scale,
+            negative: negative && digits != 0,
+        }
+    }
+
+    pub fn scale(&self) -> u16 {
+        self.scale
+    }
+
+    // while the digits fit in an f64's mantissa and the power of ten is exact too, one division gives
+    // the closest f64, the same as parsing the original text would. past that it can be an ulp out.
+    pub fn to_f64(&self) -> f64 {
+        let digits = self.digits as f64;
+        let value = match POWERS_OF_TEN.get(self.scale as usize) {
+            Some(power) if self.digits < 1 << 53 => digits / power,
+            _ => digits / 10f64.powi(self.scale as i32),
+        };
+        if self.negative { -value } else { value }
+    }
+}
+
+// every power of ten an f64 holds exactly.
+const POWERS_OF_TEN: [f64; 23] = [
+    1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11, 1e12, 1e13, 1e14, 1e15, 1e16, 1e17, 1e18,
+    1e19, 1e20, 1e21, 1e22,
+];
+
+impl FromStr for Decimal {
+    type Err = ParseDecimalError;
+
+    // plain decimal notation only: an optional minus sign, at least one digit and optionally a point
+    // followed by at least one more. no exponents, since "1e3" can't be written back the same way.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let (whole, fraction) = match unsigned.split_once('.') {
+            Some((whole, fraction)) => (whole, fraction),
+            None => (unsigned, ""),
+        };
+
+        let is_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+        if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+            return Err(ParseDecimalError);
+        }
+        if unsigned.ends_with('.') {
+            return Err(ParseDecimalError);
+        }
+
+        let mut digits: u64 = 0;
+        for byte in whole.bytes().chain(fraction.bytes()) {
+            digits = digits
+                .checked_mul(10)
+                .and_then(|digits| digits.checked_add((byte - b'0') as u64))
+                .ok_or(ParseDecimalError)?;
+        }
+
+        Ok(Self::new(digits, fraction.len() as u16, negative))
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = self.digits.to_string();
+        let scale = self.scale as usize;
+        if self.negative {
+            write!(f, "-")?;
+        }
+        if scale == 0 {
+            return write!(f, "{}", digits);
+        }
+
+        // there's always a digit before the point, 5 with a scale of 2 is "0.05".
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}.{}", whole, fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for text in ["0", "12", "-35.70", "2.71", "0.05", "0.00", "1.10", "3000000000.5", "9999999999999999999"] {
+            let decimal: Decimal = text.parse().unwrap();
+            assert_eq!(decimal.to_string(), text);
+        }
+        assert_eq!("-35.70".parse::<Decimal>().unwrap().to_f64(), -35.7);
+        assert_eq!("-0.00".parse::<Decimal>().unwrap(), "0.00".parse::<Decimal>().unwrap());
+        assert_eq!("-0.00".parse::<Decimal>().unwrap().to_string(), "0.00");
+        assert_eq!(Decimal::new(0, 1, true), Decimal::new(0, 1, false));
+    }
+
+    #[test]
+    fn test_to_f64() {
+        for text in ["0.1", "-2.71", "123456.789", "9999999999999999999", "1.0000000000000002"] {
+            assert_eq!(text.parse::<Decimal>().unwrap().to_f64(), text.parse::<f64>().unwrap(), "{:?}", text);
+        }
+        // too many places for an exact power of ten, it's only close.
+        let tiny = "0.0000000000000000000000000001".parse::<Decimal>().unwrap().to_f64();
+        assert!((tiny - 1e-28).abs() <= 1e-28 * f64::EPSILON * 2.0);
+        assert!("-0.00".parse::<Decimal>().unwrap().to_f64().is_sign_positive());
+        assert_eq!("0.05".parse::<Decimal>().unwrap(), Decimal::new(5, 2, false));
+    }
+
+    #[test]
+    fn test_invalid() {
+        for text in ["", "-", ".5", "5.", "1e3", "+1", "1.2.3", "a", "1 ", "99999999999999999999"] {
+            assert_eq!(text.parse::<Decimal>(), Err(ParseDecimalError), "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,619 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use crate::excel::{CellAddress, FieldValue, Grid, Text, Value};
+use crate::formual::{Ast, Node, Operator, ParseError};
+use crate::workbook::Workbook;
+
+// the functions formulas can call.
+pub const FUNCTIONS: [&str; 10] = ["SUM", "AVERAGE", "MIN", "MAX", "COUNT", "COUNTA", "IF", "AND", "OR", "NOT"];
+
+pub fn function_names() -> HashSet<String> {
+    FUNCTIONS.iter().map(|name| name.to_string()).collect()
+}
+
+// what a formula works out to when it can't be worked out, written the way excel shows them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellError {
+    Ref, // a reference to a sheet that doesn't exist, or a cell that can't exist.
+    Div0,
+    Value, // the wrong kind of value, like adding text to a number.
+    Name, // a formula that doesn't parse, usually an unknown function.
+    Cycle, // the formula depends on itself.
+    Depth, // formulas too deep to work out, see MAX_DEPTH.
+}
+
+impl fmt::Display for CellError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CellError::Ref => write!(f, "#REF!"),
+            CellError::Div0 => write!(f, "#DIV/0!"),
+            CellError::Value => write!(f, "#VALUE!"),
+            CellError::Name => write!(f, "#NAME?"),
+            // excel shows 0 for these and warns about them separately. there's nowhere for a warning
+            // to go here so they get errors of their own, rather than google sheets' #REF! which
+            // looks the same as a reference that's really broken.
+            CellError::Cycle => write!(f, "#CYCLE!"),
+            CellError::Depth => write!(f, "#DEPTH!"),
+        }
+    }
+}
+
+// the value of a cell once its formula is worked out. numbers are all f64 the same as in excel.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Computed {
+    Empty,
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Error(CellError),
+}
+
+impl Computed {
+    // the number for some arithmetic. blanks are 0, TRUE is 1 and text is fine as long as it's a
+    // number, like "3".
+    fn number(&self) -> Result<f64, CellError> {
+        match self {
+            Computed::Empty => Ok(0.0),
+            Computed::Number(n) => Ok(*n),
+            Computed::Bool(b) => Ok(if *b { 1.0 } else { 0.0 }),
+            Computed::Text(s) => s.trim().parse().map_err(|_| CellError::Value),
+            Computed::Error(err) => Err(*err),
+        }
+    }
+
+    // the condition in an IF, AND and so on. any number other than 0 is true.
+    fn truth(&self) -> Result<bool, CellError> {
+        match self {
+            Computed::Text(s) if s.eq_ignore_ascii_case("true") => Ok(true),
+            Computed::Text(s) if s.eq_ignore_ascii_case("false") => Ok(false),
+            Computed::Text(_) => Err(CellError::Value),
+            value => value.number().map(|n| n != 0.0),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Computed::Error(_))
+    }
+
+    // blanks are null and errors are strings like "#REF!". numbers json can't hold, like the
+    // infinity from adding up very big ones, are null too.
+    pub fn to_json(&self) -> String {
+        match self {
+            Computed::Empty => "null".to_string(),
+            Computed::Number(n) if !n.is_finite() => "null".to_string(),
+            Computed::Bool(b) => b.to_string(),
+            Computed::Number(_) => self.to_string(),
+            Computed::Text(s) => json_string(s),
+            Computed::Error(err) => json_string(&err.to_string()),
+        }
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl From<Value<'_>> for Computed {
+    // for cells without a formula. dates can't be used in arithmetic, they're only passed along.
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Empty => Computed::Empty,
+            Value::Bool(b) => Computed::Bool(b),
+            Value::String(s) | Value::Formula(s) => Computed::Text(s.to_string()),
+            Value::Date(d) => Computed::Text(d.to_string()),
+            value => value.as_f64().map_or(Computed::Error(CellError::Value), Computed::Number),
+        }
+    }
+}
+
+impl From<Computed> for FieldValue {
+    // whole numbers go back to integers so 1+2 is written as 3 and not 3.0. errors are kept as text.
+    fn from(value: Computed) -> Self {
+        match value {
+            Computed::Empty => FieldValue::Empty,
+            Computed::Number(n) => whole(n).map_or(FieldValue::Float(n), FieldValue::Integer),
+            Computed::Text(s) => FieldValue::String(Text::from(s)),
+            Computed::Bool(b) => FieldValue::Bool(b),
+            Computed::Error(err) => FieldValue::String(Text::from(err.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Computed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Computed::Empty => Ok(()),
+            Computed::Number(n) => match whole(*n) {
+                Some(i) => write!(f, "{}", i),
+                None => write!(f, "{}", n),
+            },
+            Computed::Text(s) => write!(f, "{}", s),
+            Computed::Bool(true) => write!(f, "TRUE"),
+            Computed::Bool(false) => write!(f, "FALSE"),
+            Computed::Error(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+// n as an integer if it's a whole number small enough for an f64 to hold exactly.
+fn whole(n: f64) -> Option<i64> {
+    (n.fract() == 0.0 && n.abs() < 9_007_199_254_740_992.0).then_some(n as i64)
+}
+
+// how deep formulas can go, counting each part of a formula and each cell it leads on to, before
+// the rest is given up on as #DEPTH! rather than running out of stack. a chain like A2=A1+1, A3=A2+1
+// down a column gets about 250 cells deep when worked out from the bottom, cells that are already
+// worked out don't count so it goes any distance from the top.
+const MAX_DEPTH: usize = 1000;
+
+// works out formulas over one or more sheets. each cell is only worked out once, so a grid full of
+// formulas pointing at the same cells stays quick, which also means the sheets can't change while
+// the evaluator is around.
+pub struct Evaluator<'a> {
+    sheets: Vec<(&'a str, &'a dyn Grid)>,
+    functions: HashSet<String>,
+    cache: HashMap<(usize, CellAddress), Computed>,
+    pending: HashSet<(usize, CellAddress)>,
+    depth: usize,
+    // cells that came out #DEPTH! only because of how deep they were reached from, they're worked
+    // out again next time rather than being stuck like that.
+    too_deep: Vec<(usize, CellAddress)>,
+}
+
+impl<'a> Evaluator<'a> {
+    // a single sheet, references to other sheets are #REF!.
+    pub fn new(grid: &'a dyn Grid) -> Self {
+        Self::with_sheets(vec![("", grid)])
+    }
+
+    // sheets are numbered in the order they're in the workbook.
+    pub fn for_workbook(workbook: &'a Workbook) -> Self {
+        Self::with_sheets(
+            workbook
+                .sheets
+                .iter()
+                .map(|sheet| (sheet.name.as_str(), &sheet.excel as &dyn Grid))
+                .collect(),
+        )
+    }
+
+    fn with_sheets(sheets: Vec<(&'a str, &'a dyn Grid)>) -> Self {
+        Self {
+            sheets,
+            functions: function_names(),
+            cache: HashMap::new(),
+            pending: HashSet::new(),
+            depth: 0,
+            too_deep: Vec::new(),
+        }
+    }
+
+    // the value of a cell on the numbered sheet, with its formula worked out if it has one.
+    pub fn cell(&mut self, sheet: usize, address: CellAddress) -> Computed {
+        let grid = match self.sheets.get(sheet) {
+            Some((_, grid)) => *grid,
+            None => return Computed::Error(CellError::Ref),
+        };
+        let src = match grid.value(address) {
+            Some(Value::Formula(src)) => src,
+            Some(value) => return Computed::from(value),
+            None => return Computed::Empty,
+        };
+
+        let key = (sheet, address);
+        if let Some(value) = self.cache.get(&key) {
+            return value.clone();
+        }
+        if !self.pending.insert(key) {
+            return Computed::Error(CellError::Cycle);
+        }
+        let value = self.formula(sheet, src);
+        self.pending.remove(&key);
+        // too deep is still cached for now, or a grid of cells each using the one above twice would
+        // be worked out again for every path through it.
+        if value == Computed::Error(CellError::Depth) {
+            self.too_deep.push(key);
+        }
+        self.cache.insert(key, value.clone());
+        self.forget_too_deep();
+        value
+    }
+
+    // works out a formula like "=SUM(A1:A3)" as if it was in a cell on the numbered sheet.
+    pub fn formula(&mut self, sheet: usize, src: &str) -> Computed {
+        let node = match Ast::new(src, &self.functions).parse() {
+            Ok(node) => node,
+            Err(ParseError::TooDeep) => return Computed::Error(CellError::Depth),
+            Err(_) => return Computed::Error(CellError::Name),
+        };
+        let value = match self.eval(sheet, &node) {
+            // =A1 pointing at a blank cell shows 0, same as excel.
+            Computed::Empty => Computed::Number(0.0),
+            value => value,
+        };
+        self.forget_too_deep();
+        value
+    }
+
+    // once the outermost cell or formula is worked out, the cells that were too deep from there
+    // might not be from the next one.
+    fn forget_too_deep(&mut self) {
+        if self.depth == 0 {
+            for key in self.too_deep.drain(..) {
+                self.cache.remove(&key);
+            }
+        }
+    }
+
+    fn eval(&mut self, sheet: usize, node: &Node) -> Computed {
+        if self.depth == MAX_DEPTH {
+            return Computed::Error(CellError::Depth);
+        }
+        self.depth += 1;
+        let value = self.eval_node(sheet, node);
+        self.depth -= 1;
+        value
+    }
+
+    fn eval_node(&mut self, sheet: usize, node: &Node) -> Computed {
+        match node {
+            Node::Formula { expr } | Node::Primary(expr) => self.eval(sheet, expr),
+
+            Node::Expression { lhs, op, rhs } | Node::Term { lhs, op, rhs } | Node::Factor { lhs, op, rhs } => {
+                match (op, rhs) {
+                    (Some(op), Some(rhs)) => {
+                        let lhs = self.eval(sheet, lhs);
+                        let rhs = self.eval(sheet, rhs);
+                        arithmetic(op, lhs, rhs).unwrap_or_else(Computed::Error)
+                    }
+                    _ => self.eval(sheet, lhs),
+                }
+            }
+
+            Node::Primitive(text) => primitive(text),
+
+            Node::Function { name, args } => self.function(sheet, name, args).unwrap_or_else(Computed::Error),
+
+            // a reference on its own is the cell it points at, a range has to go to a function.
+            reference => match self.reference(sheet, reference) {
+                Some(Ok(mut values)) if values.len() == 1 && !matches!(reference, Node::CellRange(..)) => values.remove(0),
+                Some(Ok(_)) => Computed::Error(CellError::Value),
+                Some(Err(err)) => Computed::Error(err),
+                None => Computed::Error(CellError::Value),
+            },
+        }
+    }
+
+    // the values a reference points at, or None if the node isn't a reference. brackets and the
+    // like around it are looked through, so SUM((A1)) is still a reference.
+    fn reference(&mut self, sheet: usize, node: &Node) -> Option<Result<Vec<Computed>, CellError>> {
+        match node {
+            Node::Formula { expr } | Node::Primary(expr) => self.reference(sheet, expr),
+            Node::Expression { lhs, op: None, .. } | Node::Term { lhs, op: None, .. } | Node::Factor { lhs, op: None, .. } => {
+                self.reference(sheet, lhs)
+            }
+
+            Node::SheetRef(name, reference) => {
+                let index = self.sheets.iter().position(|(sheet, _)| sheet.eq_ignore_ascii_case(name));
+                match index {
+                    Some(index) => self.reference(index, reference),
+                    None => Some(Err(CellError::Ref)),
+                }
+            }
+
+            Node::CellRef(reference) => Some(match CellAddress::parse(reference) {
+                Some(address) => Ok(vec![self.cell(sheet, address)]),
+                None => Err(CellError::Ref),
+            }),
+
+            Node::CellRange(start, end) => Some(match (start.as_ref(), end.as_ref()) {
+                (Node::CellRef(start), Node::CellRef(end)) => match (CellAddress::parse(start), CellAddress::parse(end)) {
+                    (Some(start), Some(end)) => Ok(self.range(sheet, start, end)),
+                    _ => Err(CellError::Ref),
+                },
+                _ => Err(CellError::Ref),
+            }),
+
+            _ => None,
+        }
+    }
+
+    // cells outside of the loaded grid are blank, so they're left out the same as Grid::range.
+    fn range(&mut self, sheet: usize, start: CellAddress, end: CellAddress) -> Vec<Computed> {
+        let (rows, cols) = match self.sheets.get(sheet) {
+            Some((_, grid)) => (grid.row_count(), grid.column_count()),
+            None => return vec![Computed::Error(CellError::Ref)],
+        };
+
+        let mut values = Vec::new();
+        for row in start.row.min(end.row)..=start.row.max(end.row).min(rows.saturating_sub(1)) {
+            for col in start.col.min(end.col)..=start.col.max(end.col).min(cols.saturating_sub(1)) {
+                values.push(self.cell(sheet, CellAddress { row, col }));
+            }
+        }
+        values
+    }
+
+    fn function(&mut self, sheet: usize, name: &str, args: &[Node]) -> Result<Computed, CellError> {
+        match name {
+            // IF only works out the branch it takes.
+            "IF" => {
+                if args.len() < 2 || args.len() > 3 {
+                    return Err(CellError::Value);
+                }
+                if self.eval(sheet, &args[0]).truth()? {
+                    Ok(self.eval(sheet, &args[1]))
+                } else if let Some(otherwise) = args.get(2) {
+                    Ok(self.eval(sheet, otherwise))
+                } else {
+                    Ok(Computed::Bool(false))
+                }
+            }
+
+            "NOT" => match args {
+                [arg] => Ok(Computed::Bool(!self.eval(sheet, arg).truth()?)),
+                _ => Err(CellError::Value),
+            },
+
+            "COUNTA" => {
+                let values = self.arguments(sheet, args)?;
+                let count = values.iter().filter(|arg| !matches!(arg, Argument::Referenced(Computed::Empty))).count();
+                Ok(Computed::Number(count as f64))
+            }
+
+            // COUNT doesn't mind errors or text, it only counts numbers.
+            "COUNT" => {
+                let values = self.arguments(sheet, args)?;
+                let count = values
+                    .iter()
+                    .filter(|arg| match arg {
+                        Argument::Referenced(value) => matches!(value, Computed::Number(_)),
+                        Argument::Given(value) => value.number().is_ok() && !matches!(value, Computed::Empty),
+                    })
+                    .count();
+                Ok(Computed::Number(count as f64))
+            }
+
+            "AND" | "OR" => {
+                let mut conditions = Vec::new();
+                for arg in self.arguments(sheet, args)? {
+                    match arg {
+                        // text and blanks in a range are skipped.
+                        Argument::Referenced(Computed::Text(_) | Computed::Empty) => {}
+                        Argument::Referenced(value) | Argument::Given(value) => conditions.push(value.truth()?),
+                    }
+                }
+                if conditions.is_empty() {
+                    return Err(CellError::Value);
+                }
+                let result = if name == "AND" {
+                    conditions.iter().all(|c| *c)
+                } else {
+                    conditions.iter().any(|c| *c)
+                };
+                Ok(Computed::Bool(result))
+            }
+
+            _ => {
+                let numbers = self.numbers(sheet, args)?;
+                let result = match name {
+                    "SUM" => numbers.iter().sum(),
+                    "AVERAGE" if numbers.is_empty() => return Err(CellError::Div0),
+                    "AVERAGE" => numbers.iter().sum::<f64>() / numbers.len() as f64,
+                    "MIN" => numbers.iter().copied().reduce(f64::min).unwrap_or(0.0),
+                    "MAX" => numbers.iter().copied().reduce(f64::max).unwrap_or(0.0),
+                    // the parser only lets through known functions.
+                    _ => return Err(CellError::Name),
+                };
+                Ok(Computed::Number(result))
+            }
+        }
+    }
+
+    // every argument's values, ranges spread out into their cells. errors anywhere are the result of
+    // the function, except for COUNT and COUNTA which look past them.
+    fn arguments(&mut self, sheet: usize, args: &[Node]) -> Result<Vec<Argument>, CellError> {
+        let mut values = Vec::new();
+        for arg in args {
+            match self.reference(sheet, arg) {
+                Some(referenced) => values.extend(referenced?.into_iter().map(Argument::Referenced)),
+                None => values.push(Argument::Given(self.eval(sheet, arg))),
+            }
+        }
+        Ok(values)
+    }
+
+    // the numbers for SUM and friends. numbers typed into the formula have to be numbers, text and
+    // blanks in the cells it points at are skipped.
+    fn numbers(&mut self, sheet: usize, args: &[Node]) -> Result<Vec<f64>, CellError> {
+        let mut numbers = Vec::new();
+        for arg in self.arguments(sheet, args)? {
+            match arg {
+                Argument::Referenced(Computed::Number(n)) => numbers.push(n),
+                Argument::Referenced(Computed::Error(err)) => return Err(err),
+                Argument::Referenced(_) => {}
+                Argument::Given(value) => numbers.push(value.number()?),
+            }
+        }
+        Ok(numbers)
+    }
+}
+
+// a function argument's value, either from the cells it points at or typed into the formula.
+enum Argument {
+    Referenced(Computed),
+    Given(Computed),
+}
+
+// literals in a formula, strings in quotes lose them in the parser so "3" is the same as 3.
+fn primitive(text: &str) -> Computed {
+    if text.eq_ignore_ascii_case("true") {
+        Computed::Bool(true)
+    } else if text.eq_ignore_ascii_case("false") {
+        Computed::Bool(false)
+    } else {
+        match text.parse() {
+            Ok(n) => Computed::Number(n),
+            Err(_) => Computed::Text(text.to_string()),
+        }
+    }
+}
+
+fn arithmetic(op: &Operator, lhs: Computed, rhs: Computed) -> Result<Computed, CellError> {
+    let (lhs, rhs) = (lhs.number()?, rhs.number()?);
+    if op.is_division() && rhs == 0.0 {
+        return Err(CellError::Div0);
+    }
+    op.apply(lhs, rhs).map(Computed::Number).ok_or(CellError::Value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::excel::Excel;
+
+    fn eval(src: &str, formula: &str) -> Computed {
+        let excel = Excel::new(src.to_string());
+        Evaluator::new(&excel).formula(0, formula)
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(eval("", "=1+2*3"), Computed::Number(7.0));
+        assert_eq!(eval("", "=10-4-3"), Computed::Number(3.0));
+        assert_eq!(eval("", "=8/4/2"), Computed::Number(1.0));
+        assert_eq!(eval("", "=(1+2)*3"), Computed::Number(9.0));
+        assert_eq!(eval("", "=-1.5*2"), Computed::Number(-3.0));
+        assert_eq!(eval("", "=1/0"), Computed::Error(CellError::Div0));
+        assert_eq!(eval("", "=FOO(1)"), Computed::Error(CellError::Name));
+
+        // blanks are 0, text has to be a number.
+        assert_eq!(eval("1,,x,\"4\"\n", "=A1+B1"), Computed::Number(1.0));
+        assert_eq!(eval("1,,x,\"4\"\n", "=A1+C1"), Computed::Error(CellError::Value));
+        assert_eq!(eval("1,,x,\"4\"\n", "=A1+D1"), Computed::Number(5.0));
+        assert_eq!(eval("1,,x\n", "=B1"), Computed::Number(0.0));
+        assert_eq!(eval("1,,x\n", "=C1"), Computed::Text("x".to_string()));
+        assert_eq!(eval("", "=\"a b\""), Computed::Text("a b".to_string()));
+        assert_eq!(eval("", "=\"it's \"\"1\"\"!\""), Computed::Text("it's \"1\"!".to_string()));
+    }
+
+    #[test]
+    fn test_functions() {
+        let src = "1,2,,x\n3,TRUE,=1/0\n";
+        assert_eq!(eval(src, "=SUM(A1:D1)"), Computed::Number(3.0));
+        assert_eq!(eval(src, "=SUM(A1:B2)"), Computed::Number(6.0));
+        assert_eq!(eval(src, "=SUM(A1:C2)"), Computed::Error(CellError::Div0));
+        assert_eq!(eval(src, "=AVERAGE(A1:D1)"), Computed::Number(1.5));
+        assert_eq!(eval(src, "=AVERAGE(C1:D1)"), Computed::Error(CellError::Div0));
+        assert_eq!(eval(src, "=MIN(A1:A2,5)"), Computed::Number(1.0));
+        assert_eq!(eval(src, "=MAX(A1:A2,5)"), Computed::Number(5.0));
+        assert_eq!(eval(src, "=COUNT(A1:D2)"), Computed::Number(3.0));
+        assert_eq!(eval(src, "=COUNTA(A1:D2)"), Computed::Number(6.0));
+        assert_eq!(eval(src, "=IF(B2,A1,A2)"), Computed::Number(1.0));
+        assert_eq!(eval(src, "=IF(A1-1,1,C2)"), Computed::Error(CellError::Div0));
+        assert_eq!(eval(src, "=IF(A1-1,1)"), Computed::Bool(false));
+        assert_eq!(eval(src, "=AND(A1,B2,NOT(C1))"), Computed::Bool(true));
+        assert_eq!(eval(src, "=OR(C1,D1,A1-1)"), Computed::Bool(false));
+        assert_eq!(eval(src, "=NOT(D1)"), Computed::Error(CellError::Value));
+    }
+
+    #[test]
+    fn test_to_json() {
+        assert_eq!(Computed::Empty.to_json(), "null");
+        assert_eq!(Computed::Number(3.0).to_json(), "3");
+        assert_eq!(Computed::Number(-0.25).to_json(), "-0.25");
+        assert_eq!(Computed::Number(f64::INFINITY).to_json(), "null");
+        assert_eq!(Computed::Bool(true).to_json(), "true");
+        assert_eq!(Computed::Text("say \"hi\"\n\u{1}".to_string()).to_json(), r#""say \"hi\"\n\u0001""#);
+        assert_eq!(Computed::Error(CellError::Ref).to_json(), "\"#REF!\"");
+    }
+
+    #[test]
+    fn test_cycles() {
+        let excel = Excel::new("=B1,=A1,=A1+1\n=C2,5,=B2*2\n".to_string());
+        let mut evaluator = Evaluator::new(&excel);
+        assert_eq!(evaluator.cell(0, CellAddress::parse("A1").unwrap()), Computed::Error(CellError::Cycle));
+        assert_eq!(evaluator.cell(0, CellAddress::parse("C1").unwrap()), Computed::Error(CellError::Cycle));
+        assert_eq!(evaluator.cell(0, CellAddress::parse("A2").unwrap()), Computed::Number(10.0));
+    }
+
+    #[test]
+    fn test_deep_chains() {
+        let chain = |len: usize| {
+            let mut src = "1\n".to_string();
+            for row in 1..len {
+                src += &format!("=A{}+1\n", row);
+            }
+            Excel::new(src)
+        };
+        let excel = chain(200);
+        assert_eq!(Evaluator::new(&excel).cell(0, CellAddress { row: 199, col: 0 }), Computed::Number(200.0));
+
+        // too deep to work out from the bottom, but fine once the cells above it are, with the same
+        // evaluator. the cells in between aren't stuck on the error.
+        let excel = chain(5000);
+        let mut evaluator = Evaluator::new(&excel);
+        assert_eq!(evaluator.cell(0, CellAddress { row: 4999, col: 0 }), Computed::Error(CellError::Depth));
+        assert_eq!(evaluator.cell(0, CellAddress { row: 4000, col: 0 }).to_string(), "#DEPTH!");
+        assert_eq!(evaluator.formula(0, "=SUM(A1:A5000)"), Computed::Number(5000.0 * 5001.0 / 2.0));
+        assert_eq!(evaluator.cell(0, CellAddress { row: 4999, col: 0 }), Computed::Number(5000.0));
+
+        // each cell using the one above twice doesn't take forever to give up on.
+        let mut src = "1\n".to_string();
+        for row in 1..2000 {
+            src += &format!("=A{}+A{}\n", row, row);
+        }
+        let excel = Excel::new(src);
+        let mut evaluator = Evaluator::new(&excel);
+        assert_eq!(evaluator.cell(0, CellAddress { row: 1999, col: 0 }), Computed::Error(CellError::Depth));
+
+        // and the same goes for one formula nested too deep.
+        let formula = |depth| format!("={}1{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(evaluator.formula(0, &formula(50)), Computed::Number(1.0));
+        assert_eq!(evaluator.formula(0, &formula(100_000)), Computed::Error(CellError::Depth));
+    }
+
+    #[test]
+    fn test_formulas_file() {
+        let excel = Excel::from_path("test_data/formulas.csv").unwrap();
+        let mut evaluator = Evaluator::new(&excel);
+        let mut row = |row| -> Vec<String> {
+            (4..6).map(|col| evaluator.cell(0, CellAddress { row, col }).to_string()).collect()
+        };
+        assert_eq!(row(0), ["#VALUE!", "126.14"]);
+        // the average skips the text in B2 but not the error in E2.
+        assert_eq!(row(1), ["#VALUE!", "#VALUE!"]);
+        // F3 is in its own MAX.
+        assert_eq!(row(2), ["#VALUE!", "#CYCLE!"]);
+    }
+
+    #[test]
+    fn test_workbook() {
+        let workbook = Workbook::from_dir("test_data/workbook").unwrap();
+        let sheet = workbook.sheet_names().iter().position(|name| *name == "Sheet1").unwrap();
+        let mut evaluator = Evaluator::for_workbook(&workbook);
+        assert_eq!(evaluator.cell(sheet, CellAddress::parse("A2").unwrap()), Computed::Number(10.0));
+        // 'My Sheet' is all text, so there's nothing to add up.
+        assert_eq!(evaluator.cell(sheet, CellAddress::parse("B2").unwrap()), Computed::Number(0.0));
+        assert_eq!(evaluator.formula(sheet, "=SUM(Sheet2!A1:B2)+C2"), Computed::Number(106.0));
+        assert_eq!(evaluator.formula(sheet, "=Missing!A1"), Computed::Error(CellError::Ref));
+
+        // without the workbook there are no other sheets.
+        let mut evaluator = Evaluator::new(workbook.sheet("Sheet1").unwrap());
+        assert_eq!(evaluator.formula(0, "=Sheet2!A1"), Computed::Error(CellError::Ref));
+    }
+}
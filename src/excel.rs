@@ -1,130 +1,1222 @@
-use std::fs::{read_to_string, File};
-use std::io::{BufWriter, Write};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
 use std::fmt;
-use crate::csv::Parser;
+use std::ops::Deref;
+use std::sync::Arc;
+use crate::csv::{map_chunks, sniff, CsvError, CsvField, Dialect, Parser, Quoting, Reader, Rows, Warning};
+use crate::date::Date;
+use crate::decimal::Decimal;
+use crate::eval::Evaluator;
+use crate::interner::Interner;
+use crate::schema::{Schema, SchemaError};
+use crate::source::Source;
+use crate::xlsx::{read_sheets, XlsxError};
 
-#[derive(Debug)]
+// the text of a string field. fields read out of a mapped file point back into it rather than
+// having their own copy, and the rest share one copy of each distinct string from a pool. a field
+// only gets a copy of its own once its text is changed.
+#[derive(Clone)]
+pub enum Text {
+    Shared {
+        source: Arc<Source>,
+        start: usize,
+        end: usize,
+    },
+    Pooled(Arc<str>),
+    Owned(String),
+}
+
+impl Text {
+    // `item` is shared when it was borrowed from `source`, anything else comes from the pool.
+    fn new(item: Cow<str>, source: Option<&Arc<Source>>, pool: &mut Interner) -> Self {
+        match (item, source) {
+            (Cow::Borrowed(item), Some(source)) => {
+                let start = item.as_ptr() as usize - source.as_ptr() as usize;
+                debug_assert!(start + item.len() <= source.len());
+                Text::Shared {
+                    source: Arc::clone(source),
+                    start,
+                    end: start + item.len(),
+                }
+            }
+            (item, _) => Text::Pooled(pool.intern_shared(&item)),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            // SAFETY: shared text is only ever made in Text::new from a &str the parser had already
+            // checked was valid utf-8, and the source underneath it is never changed from in here.
+            Text::Shared { source, start, end } => unsafe { std::str::from_utf8_unchecked(&source[*start..*end]) },
+            Text::Pooled(text) => text,
+            Text::Owned(text) => text,
+        }
+    }
+
+    pub fn is_shared(&self) -> bool {
+        matches!(self, Text::Shared { .. })
+    }
+
+    // copies the text out of the source or pool the first time it's changed.
+    pub fn to_mut(&mut self) -> &mut String {
+        if !matches!(self, Text::Owned(_)) {
+            *self = Text::Owned(self.as_str().to_string());
+        }
+        match self {
+            Text::Owned(text) => text,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Deref for Text {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<String> for Text {
+    fn from(text: String) -> Self {
+        Text::Owned(text)
+    }
+}
+
+impl From<&str> for Text {
+    fn from(text: &str) -> Self {
+        Text::Owned(text.to_string())
+    }
+}
+
+impl PartialEq for Text {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl PartialEq<str> for Text {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Text {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum FieldValue {
-    Integer(i32),
-    Float(f32),
-    String(String),
+    Empty, // a blank cell, which isn't the same as a cell holding an empty string.
+    Integer(i64),
+    Float(f64),
+    Decimal(Decimal),
+    Bool(bool),
+    Date(Date),
+    String(Text),
     Formula(String)
 }
 
 impl FieldValue {
-    fn new(item: String) -> Self {
-        // to-do, what if its the string "123" and not an int?
-        if let Ok(int) = item.parse::<i32>() {
-            FieldValue::Integer(int)
-        } else if let Ok(float) = item.parse::<f32>() {
-            FieldValue::Float(float)
-        } else {
-            let chars = item.chars().collect::<Vec<_>>();
-            if chars.len() > 0 && chars[0] == '=' {
-                return FieldValue::Formula(item);
+    // the field as it should be written to a csv file in the given dialect.
+    pub fn to_csv(&self, dialect: &Dialect) -> String {
+        self.as_value().to_csv(dialect)
+    }
+
+    pub fn as_value(&self) -> Value<'_> {
+        match self {
+            FieldValue::Empty => Value::Empty,
+            FieldValue::Integer(i) => Value::Integer(*i),
+            FieldValue::Float(fl) => Value::Float(*fl),
+            FieldValue::Decimal(d) => Value::Decimal(*d),
+            FieldValue::Bool(b) => Value::Bool(*b),
+            FieldValue::Date(d) => Value::Date(*d),
+            FieldValue::String(s) => Value::String(s),
+            FieldValue::Formula(s) => Value::Formula(s),
+        }
+    }
+}
+
+// a borrowed look at a cell's value, it doesn't matter how the cell is stored underneath.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value<'a> {
+    Empty,
+    Integer(i64),
+    Float(f64),
+    Decimal(Decimal),
+    Bool(bool),
+    Date(Date),
+    String(&'a str),
+    Formula(&'a str),
+}
+
+impl<'a> Value<'a> {
+    // with `decimals` set, numbers with a decimal point are read as exact decimals instead of floats.
+    pub fn parse(item: &'a str, decimals: bool) -> Self {
+        if item.is_empty() {
+            return Value::Empty;
+        }
+        if let Ok(int) = item.parse::<i64>() {
+            return Value::Integer(int);
+        }
+        if decimals {
+            if let Ok(decimal) = item.parse::<Decimal>() {
+                return Value::Decimal(decimal);
             }
-            return FieldValue::String(item);
+        }
+
+        match item.parse::<f64>() {
+            Ok(float) => Value::Float(float),
+            Err(_) => Value::text(item),
+        }
+    }
+
+    // a field read from a csv file. quoting a number is how a file says it's text, like the zip code
+    // "00123", so quoted fields are only read as numbers when the dialect asks for it. an empty field
+    // is a blank cell, quoted or not, unless the dialect reads "" as an empty string.
+    pub fn from_csv(field: &'a CsvField, dialect: Dialect) -> Self {
+        if field.text.is_empty() && !(field.quoted && dialect.empty_strings) {
+            Value::Empty
+        } else if field.quoted && !dialect.quoted_numbers {
+            Value::text(&field.text)
+        } else {
+            Value::parse(&field.text, dialect.decimals)
         }
     }
 
-    fn fmt_string_field(&self, item: &str) -> String {
-        let mut out = String::new();
-        let special_chars = ['"', ',', '\n', '\r'];
+    fn text(item: &'a str) -> Self {
+        if item.starts_with('=') {
+            Value::Formula(item)
+        } else {
+            Value::String(item)
+        }
+    }
 
-        for sp_c in special_chars {
-            if item.contains(sp_c) {
-                out.push('"');
-                for rest in item.chars() {
-                    if rest == '"' {
-                        out.push('"');
-                        out.push('"');
-                    } else {
-                        out.push(rest);
-                    }
+    // the value as it should be written to a csv file in the given dialect, quoted according to its
+    // quoting policy.
+    pub fn to_csv(&self, dialect: &Dialect) -> String {
+        let text = match self {
+            Value::Empty => return String::new(),
+            Value::String(s) | Value::Formula(s) => Cow::Borrowed(*s),
+            value => Cow::Owned(value.to_string()),
+        };
+
+        let special = dialect.needs_quotes(&text);
+        let quote = match dialect.quoting {
+            Quoting::Minimal => self.needs_quotes(&text, dialect),
+            Quoting::All => true,
+            Quoting::NonNumeric => self.as_f64().is_none() || self.needs_quotes(&text, dialect),
+            Quoting::Never => self.needs_quotes(&text, dialect) && !(special && dialect.escape.is_some()),
+        };
+        if quote {
+            dialect.quote(&text)
+        } else if special {
+            dialect.escape_field(&text)
+        } else {
+            text.into_owned()
+        }
+    }
+
+    // whether `text`, how the value is written, has to be quoted to be read back the same: it has
+    // special characters in it, or it's text that would otherwise be read back as a number or blank.
+    fn needs_quotes(&self, text: &str, dialect: &Dialect) -> bool {
+        dialect.needs_quotes(text)
+            || match self {
+                Value::String(s) => {
+                    s.is_empty() || (!dialect.quoted_numbers && Value::parse(s, dialect.decimals).as_f64().is_some())
                 }
-                out.push('"');
-                return out;
+                _ => false,
             }
+    }
+
+    // numbers as a float, for sums and the like. anything else isn't a number, blank cells included,
+    // so they're skipped when counting or averaging.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Float(fl) => Some(*fl),
+            Value::Decimal(d) => Some(d.to_f64()),
+            _ => None,
+        }
+    }
+
+    // the value as one side of some arithmetic, like =A1+1, where a blank cell counts as 0.
+    pub fn as_operand(&self) -> Option<f64> {
+        match self {
+            Value::Empty => Some(0.0),
+            value => value.as_f64(),
         }
+    }
 
-        return item.to_string();
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Value::Empty)
     }
 }
 
-impl fmt::Display for FieldValue {
+impl From<Value<'_>> for FieldValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Empty => FieldValue::Empty,
+            Value::Integer(i) => FieldValue::Integer(i),
+            Value::Float(fl) => FieldValue::Float(fl),
+            Value::Decimal(d) => FieldValue::Decimal(d),
+            Value::Bool(b) => FieldValue::Bool(b),
+            Value::Date(d) => FieldValue::Date(d),
+            Value::String(s) => FieldValue::String(Text::from(s)),
+            Value::Formula(s) => FieldValue::Formula(s.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Value<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            FieldValue::Integer(i) => write!(f, "{}", i),
-            FieldValue::Float(fl) => write!(f, "{}", fl),
-            FieldValue::String(s) => write!(f, "{}", self.fmt_string_field(&s)),
-            FieldValue::Formula(s) => write!(f, "{}", self.fmt_string_field(&s)),
+            Value::Empty => Ok(()),
+            Value::Integer(i) => write!(f, "{}", i),
+            // the shortest text that reads back as the same f64, so "2.71" stays "2.71". whole numbers
+            // keep a ".0" so they're read back as floats and not integers.
+            Value::Float(fl) => write!(f, "{:?}", fl),
+            Value::Decimal(d) => write!(f, "{}", d),
+            Value::Bool(true) => write!(f, "TRUE"),
+            Value::Bool(false) => write!(f, "FALSE"),
+            Value::Date(d) => write!(f, "{}", d),
+            Value::String(s) => write!(f, "{}", Dialect::default().quote_field(s)),
+            Value::Formula(s) => write!(f, "{}", Dialect::default().quote_field(s)),
         }
     }
 }
 
+// read access to a grid of cells, implemented by both the row based Excel and the Columnar backend.
+// cells past the end of a row, or rows past the end of the grid, are None.
+pub trait Grid {
+    fn value(&self, address: CellAddress) -> Option<Value<'_>>;
+
+    fn row_count(&self) -> usize;
+
+    fn column_count(&self) -> usize;
+
+    // every cell in a rectangle going down each column in turn, missing cells are left out.
+    fn range(&self, start: CellAddress, end: CellAddress) -> Vec<Value<'_>> {
+        let mut values = Vec::new();
+        for col in start.col.min(end.col)..=start.col.max(end.col) {
+            for row in start.row.min(end.row)..=start.row.max(end.row) {
+                values.extend(self.value(CellAddress { row, col }));
+            }
+        }
+        values
+    }
+
+    // sums the numbers in a column, text and missing cells are skipped.
+    fn column_sum(&self, col: usize) -> f64 {
+        (0..self.row_count())
+            .filter_map(|row| self.value(CellAddress { row, col })?.as_f64())
+            .sum()
+    }
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_value().fmt(f)
+    }
+}
+
+
+// how a field was written in the file it came from, so it can be written back out the same way.
+// most fields are written just the way their value would be anyway and don't need anything kept.
+#[derive(Debug, Clone, Default)]
+enum Lexeme {
+    #[default]
+    Plain, // the same as the value's to_csv.
+    Quoted, // in quotes that to_csv would have left off, like "00123" or "abc".
+    Raw(Box<RawText>), // anything else, like "-35.70" read as a float or "1e5" read as a number.
+}
+
+// a field's original text along with what it was read as, so it can be checked against the field's
+// value without parsing it again.
+#[derive(Debug, Clone)]
+struct RawText {
+    raw: Text,
+    text: Text,
+    quoted: bool,
+    dialect: Dialect, // the one it was read in.
+    // what a schema made of the text, like a date from "19/10/2026". None when the text reads as the
+    // value by itself.
+    typed: Option<FieldValue>,
+}
+
+impl RawText {
+    // the field as it was read, as long as that still gives the field's current value.
+    fn field(&self, dialect: &Dialect, value: Value) -> Option<CsvField<'_>> {
+        let field = CsvField {
+            text: Cow::Borrowed(&self.text),
+            raw: Cow::Borrowed(self.raw.as_bytes()),
+            quoted: self.quoted,
+        };
+        let same = match &self.typed {
+            Some(typed) => typed.as_value() == value,
+            None => Value::from_csv(&field, *dialect) == value,
+        };
+        same.then_some(field)
+    }
+
+    // whether the raw text reads back as the same field in `dialect`. a new delimiter or line
+    // terminator only matters if it's in the text, anything else has to be the same as before.
+    fn reads_the_same(&self, dialect: &Dialect) -> bool {
+        let read = &self.dialect;
+        (dialect.quote, dialect.escape, dialect.trim) == (read.quote, read.escape, read.trim)
+            && (dialect.delimiter == read.delimiter || !self.raw.contains(dialect.delimiter))
+            && (dialect.line_terminator == read.line_terminator || !self.raw.contains('\r'))
+    }
+}
 
 #[derive(Debug)]
 pub struct Field {
-    pub val: FieldValue
+    pub val: FieldValue,
+    lexeme: Lexeme,
 }
 
 impl Field {
-    fn new(item: String) -> Self {
-        let val = FieldValue::new(item);
+    pub fn new(val: FieldValue) -> Self {
+        Self {
+            val,
+            lexeme: Lexeme::Plain,
+        }
+    }
+
+    // the field as it should be written to a csv file in the given dialect. with minimal quoting a
+    // field read from a file is written exactly as it was there, unless its value has been changed
+    // so that the original text would now read back as something else.
+    pub fn to_csv(&self, dialect: &Dialect) -> String {
+        let value = self.val.as_value();
+        // a type from a schema isn't in the text, so the text is what's written or the schema
+        // couldn't read it back, a date as yyyy-mm-dd when the schema says dd/mm/yyyy.
+        if let Lexeme::Raw(raw) = &self.lexeme {
+            if raw.typed.is_some() && raw.field(dialect, value).is_some() {
+                return match dialect.quoting == Quoting::Minimal && raw.reads_the_same(dialect) {
+                    true => raw.raw.to_string(),
+                    false => Value::String(&raw.text).to_csv(dialect),
+                };
+            }
+        }
+        if dialect.quoting != Quoting::Minimal {
+            return value.to_csv(dialect);
+        }
+        match &self.lexeme {
+            Lexeme::Plain => {}
+            Lexeme::Quoted => {
+                let text = match value {
+                    Value::String(s) | Value::Formula(s) => Cow::Borrowed(s),
+                    number => Cow::Owned(number.to_string()),
+                };
+                let field = CsvField {
+                    text,
+                    raw: Cow::Borrowed(&[]),
+                    quoted: true,
+                };
+                if Value::from_csv(&field, *dialect) == value {
+                    return dialect.quote(&field.text);
+                }
+            }
+            Lexeme::Raw(raw) => {
+                if raw.reads_the_same(dialect) && raw.field(dialect, value).is_some() {
+                    return raw.raw.to_string();
+                }
+            }
+        }
+        value.to_csv(dialect)
+    }
+
+    // the text the field was read from before it was given a type, "1e5" rather than 100000.
+    pub fn text(&self, dialect: &Dialect) -> Cow<'_, str> {
+        let value = self.val.as_value();
+        if let Lexeme::Raw(raw) = &self.lexeme {
+            if raw.field(dialect, value).is_some() {
+                return Cow::Borrowed(&raw.text);
+            }
+        }
+        match value {
+            Value::String(s) | Value::Formula(s) => Cow::Borrowed(s),
+            value => Cow::Owned(value.to_string()),
+        }
+    }
+
+    // gives the field a type its text doesn't read as by itself, the way a schema does. the text is
+    // kept so the field is still written out the way it was read.
+    pub fn retype(&mut self, val: FieldValue, dialect: &Dialect) {
+        let old = self.val.as_value();
+        if old == val.as_value() {
+            return;
+        }
+        let (raw, text, quoted) = match &self.lexeme {
+            Lexeme::Raw(raw) if raw.field(dialect, old).is_some() => (raw.raw.clone(), raw.text.clone(), raw.quoted),
+            lexeme => {
+                let text = match &self.val {
+                    FieldValue::String(text) => text.clone(),
+                    _ => Text::from(old.to_string()),
+                };
+                let raw = match lexeme {
+                    Lexeme::Quoted => dialect.quote(&text),
+                    _ => old.to_csv(dialect),
+                };
+                let quoted = raw != *text;
+                let raw = match quoted {
+                    true => Text::from(raw),
+                    false => text.clone(),
+                };
+                (raw, text, quoted)
+            }
+        };
+        self.lexeme = Lexeme::Raw(Box::new(RawText {
+            raw,
+            text,
+            quoted,
+            dialect: *dialect,
+            typed: Some(val.clone()),
+        }));
+        self.val = val;
+    }
+}
+
+// turns parsed csv into fields. numbers are parsed straight out of the source text, strings
+// borrowed from a mapped `source` stay there and other strings are looked up in the pool.
+struct Loader<'s> {
+    source: Option<&'s Arc<Source>>,
+    pool: Interner,
+    dialect: Dialect,
+    scratch: String, // numbers are written out here to compare with how they were written in the file.
+}
+
+impl<'s> Loader<'s> {
+    fn new(source: Option<&'s Arc<Source>>, dialect: Dialect) -> Self {
         Self {
-            val
+            source,
+            pool: Interner::new(),
+            dialect,
+            scratch: String::new(),
         }
     }
+
+    fn field(&mut self, item: CsvField) -> Field {
+        let value = Value::from_csv(&item, self.dialect);
+        let lexeme = self.lexeme(value, &item);
+        let val = match value {
+            Value::String(_) => FieldValue::String(Text::new(item.text, self.source, &mut self.pool)),
+            Value::Formula(_) => FieldValue::Formula(item.text.into_owned()),
+            value => value.into(),
+        };
+        Field { val, lexeme }
+    }
+
+    // compares the raw text of a field with how its value would be written, without allocating for
+    // the common cases.
+    fn lexeme(&mut self, value: Value, item: &CsvField) -> Lexeme {
+        let raw = &item.raw;
+        let text = match value {
+            Value::String(s) | Value::Formula(s) => s,
+            number => {
+                use std::fmt::Write;
+                self.scratch.clear();
+                let _ = write!(self.scratch, "{}", number);
+                &self.scratch
+            }
+        };
+
+        if **raw == *text.as_bytes() && !value.needs_quotes(text, &self.dialect) {
+            return Lexeme::Plain;
+        }
+        if is_quoted(raw, text, &self.dialect) {
+            return match value.needs_quotes(text, &self.dialect) {
+                true => Lexeme::Plain,
+                false => Lexeme::Quoted,
+            };
+        }
+
+        let raw = match raw {
+            Cow::Borrowed(raw) => String::from_utf8_lossy(raw),
+            Cow::Owned(raw) => Cow::Owned(String::from_utf8_lossy(raw).into_owned()),
+        };
+        Lexeme::Raw(Box::new(RawText {
+            raw: Text::new(raw, self.source, &mut self.pool),
+            text: Text::new(item.text.clone(), self.source, &mut self.pool),
+            quoted: item.quoted,
+            dialect: self.dialect,
+            typed: None,
+        }))
+    }
+}
+
+// whether `raw` is exactly `text` wrapped in quotes the way the dialect would write it.
+fn is_quoted(raw: &[u8], text: &str, dialect: &Dialect) -> bool {
+    if text.contains(dialect.quote) || dialect.escape.is_some_and(|escape| text.contains(escape)) {
+        return raw == dialect.quote(text).as_bytes();
+    }
+    let mut quote = [0; 4];
+    let quote = dialect.quote.encode_utf8(&mut quote).as_bytes();
+    raw.len() == text.len() + 2 * quote.len()
+        && raw.starts_with(quote)
+        && raw.ends_with(quote)
+        && &raw[quote.len()..raw.len() - quote.len()] == text.as_bytes()
+}
+
+// each chunk parsed on its own thread gets its own pool, so a string can be stored once per chunk.
+fn to_fields(csv: Rows, source: Option<&Arc<Source>>, dialect: Dialect) -> Vec<Vec<Field>> {
+    let mut loader = Loader::new(source, dialect);
+    csv.into_iter()
+        .map(|row| row.into_iter().map(|item| loader.field(item)).collect())
+        .collect()
+}
+
+// a zero based position in the grid, parsed from a reference like "B3" (col 1, row 2).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CellAddress {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl CellAddress {
+    // the size of an excel sheet, A1 to XFD1048576. nothing reads or makes cells past it, so a file
+    // or a typo can't ask for a grid bigger than memory.
+    pub const MAX_ROWS: usize = 1_048_576;
+    pub const MAX_COLUMNS: usize = 16_384;
+
+    pub fn in_sheet(&self) -> bool {
+        self.row < Self::MAX_ROWS && self.col < Self::MAX_COLUMNS
+    }
+
+    pub fn parse(reference: &str) -> Option<Self> {
+        let split = reference.find(|c: char| !c.is_ascii_alphabetic())?;
+        let (letters, digits) = reference.split_at(split);
+        if !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let col = Self::parse_column(letters)?;
+        let row = digits.parse::<usize>().ok()?;
+        if row == 0 {
+            return None;
+        }
+
+        Some(Self {
+            row: row - 1,
+            col,
+        })
+    }
+
+    // a zero based column from its letters, "B" is 1.
+    pub fn parse_column(letters: &str) -> Option<usize> {
+        if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_alphabetic()) {
+            return None;
+        }
+
+        // columns are base 26 with no zero digit, A = 1 ... Z = 26, AA = 27.
+        let mut col = 0usize;
+        for c in letters.chars() {
+            let digit = (c.to_ascii_uppercase() as u8 - b'A') as usize + 1;
+            col = col.checked_mul(26)?.checked_add(digit)?;
+        }
+        Some(col - 1)
+    }
+
+    pub fn column_name(col: usize) -> String {
+        let mut letters = Vec::new();
+        let mut col = col + 1;
+        while col > 0 {
+            letters.push(b'A' + ((col - 1) % 26) as u8);
+            col = (col - 1) / 26;
+        }
+        letters.reverse();
+        String::from_utf8(letters).unwrap()
+    }
+}
+
+impl fmt::Display for CellAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", Self::column_name(self.col), self.row + 1)
+    }
+}
+
+// roughly how much memory a grid takes and how much of it string sharing saves. sizes are what's
+// been asked for, not counting the allocator's own overhead or spare capacity.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub cells: usize,
+    pub strings: usize,
+    pub distinct_strings: usize,
+    pub string_bytes: usize, // the text of every string cell, what it would take to give each its own copy.
+    pub stored_bytes: usize, // the string text actually on the heap.
+    pub grid_bytes: usize, // the cells themselves and the rows holding them.
+}
+
+impl MemoryReport {
+    pub fn saved_bytes(&self) -> usize {
+        self.string_bytes - self.stored_bytes
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "cells: {}", self.cells)?;
+        writeln!(f, "strings: {} ({} distinct)", self.strings, self.distinct_strings)?;
+        writeln!(
+            f,
+            "string text: {} bytes, {} stored, {} saved",
+            self.string_bytes,
+            self.stored_bytes,
+            self.saved_bytes()
+        )?;
+        write!(f, "grid: {} bytes", self.grid_bytes)
+    }
+}
+
+// what to_file_with writes for cells that hold a formula.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputMode {
+    #[default]
+    Formulas, // the formula itself, for files that are still going to be edited.
+    Values, // what the formula works out to.
+    Both, // the value, with the formula in an extra column after each column that has any formulas.
 }
 
 pub struct Excel {
-    src: Vec<char>,
     pub rows: Vec<Vec<Field>>,
+    pub dialect: Dialect,
 }
 
 impl Excel {
     pub fn new(string_src: String) -> Self {
-        let src: Vec<char> = string_src.chars().collect();
-        let mut parser = Parser::new(&src);
-        let csv = parser.parse();
-        
-        let mut rows = Vec::new();
-        let mut curr_row = Vec::new();
+        Self::with_dialect(string_src, Dialect::default()).expect("the default dialect is ascii")
+    }
 
-        for row in csv {
-            for item in row {
-                curr_row.push(Field::new(item));
-            }
-            rows.push(curr_row);
-            curr_row = Vec::new();
+    // the dialect's delimiter, quote and escape characters have to be ascii, like everywhere a
+    // dialect is given.
+    pub fn with_dialect(string_src: String, dialect: Dialect) -> Result<Self, CsvError> {
+        Self::from_bytes(string_src.as_bytes(), dialect)
+    }
+
+    // like with_dialect, but malformed csv is an error rather than being read as best it can be.
+    pub fn with_dialect_strict(string_src: String, dialect: Dialect) -> Result<Self, CsvError> {
+        Self::from_bytes_strict(string_src.as_bytes(), dialect)
+    }
+
+    // skips over malformed rows instead of failing, the rows left out are returned as warnings.
+    pub fn with_dialect_lenient(string_src: String, dialect: Dialect) -> Result<(Self, Vec<Warning>), CsvError> {
+        Self::from_bytes_lenient(string_src.as_bytes(), dialect)
+    }
+
+    // parses the raw file contents without decoding them to a string first, invalid utf-8 is replaced.
+    // big inputs are split up and parsed on every core.
+    pub fn from_bytes(src: &[u8], dialect: Dialect) -> Result<Self, CsvError> {
+        let chunks = map_chunks(src, dialect, |csv| to_fields(csv, None, dialect))?;
+        Ok(Self::from_rows(chunks.into_iter().flatten().collect(), dialect))
+    }
+
+    // string fields point back into the source instead of being copied, it's kept around for as
+    // long as any of them do.
+    pub fn from_source(source: Source, dialect: Dialect) -> Result<Self, CsvError> {
+        let source = Arc::new(source);
+        let chunks = map_chunks(&source, dialect, |csv| to_fields(csv, Some(&source), dialect))?;
+        Ok(Self::from_rows(chunks.into_iter().flatten().collect(), dialect))
+    }
+
+    // invalid utf-8 is an error here, reported with where in the file it is.
+    pub fn from_bytes_strict(src: &[u8], dialect: Dialect) -> Result<Self, CsvError> {
+        let csv = Parser::new(src, dialect)?.parse_strict()?;
+        Ok(Self::from_csv(csv, dialect))
+    }
+
+    pub fn from_source_strict(source: Source, dialect: Dialect) -> Result<Self, CsvError> {
+        let source = Arc::new(source);
+        let csv = Parser::new(&source, dialect)?.parse_strict()?;
+        Ok(Self::from_rows(to_fields(csv, Some(&source), dialect), dialect))
+    }
+
+    pub fn from_bytes_lenient(src: &[u8], dialect: Dialect) -> Result<(Self, Vec<Warning>), CsvError> {
+        let (csv, warnings) = Parser::new(src, dialect)?.parse_lenient();
+        Ok((Self::from_csv(csv, dialect), warnings))
+    }
+
+    pub fn from_source_lenient(source: Source, dialect: Dialect) -> Result<(Self, Vec<Warning>), CsvError> {
+        let source = Arc::new(source);
+        let (csv, warnings) = Parser::new(&source, dialect)?.parse_lenient();
+        Ok((Self::from_rows(to_fields(csv, Some(&source), dialect), dialect), warnings))
+    }
+
+    // builds the grid a record at a time from any buffered source, like stdin, without first reading
+    // it all into a string. the first malformed record is an error.
+    pub fn from_reader<R: BufRead>(source: R, dialect: Dialect) -> Result<Self, CsvError> {
+        let mut csv = Vec::new();
+        for record in Reader::new(source, dialect) {
+            csv.push(record?.fields);
         }
+        Ok(Self::from_csv(csv, dialect))
+    }
 
-        rows.push(curr_row);
+    fn from_csv(csv: Rows, dialect: Dialect) -> Self {
+        Self::from_rows(to_fields(csv, None, dialect), dialect)
+    }
 
+    fn from_rows(rows: Vec<Vec<Field>>, dialect: Dialect) -> Self {
         Self {
-            src,
-            rows
+            rows,
+            dialect,
         }
     }
 
-    pub fn from_path(path: &str) -> std::io::Result<Self> {
-        let file_contents = read_to_string(&path)?;
-        Ok(Self::new(file_contents))
+    pub fn get(&self, address: CellAddress) -> Option<&Field> {
+        self.rows.get(address.row)?.get(address.col)
     }
 
-    pub fn to_file(&self, path: &str) -> std::io::Result<()> {
-        let file = File::create(path)?;
-        let mut writer = BufWriter::new(file);
+    pub fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport {
+            grid_bytes: self.rows.len() * size_of::<Vec<Field>>(),
+            ..MemoryReport::default()
+        };
+        // pooled strings are told apart by where their one copy is, and so are mapped ones.
+        let mut seen = HashSet::new();
+        let mut seen_raw = HashSet::new();
+
         for row in &self.rows {
-            let mut row_output = Vec::new();
+            report.cells += row.len();
+            report.grid_bytes += row.len() * size_of::<Field>();
             for field in row {
-                row_output.extend_from_slice(field.val.to_string().as_bytes());
-                row_output.push(b',');
+                // the original text of a field that doesn't match its value, like "-35.70" read as a float.
+                if let Lexeme::Raw(raw) = &field.lexeme {
+                    report.grid_bytes += size_of::<RawText>();
+                    for text in [&raw.raw, &raw.text] {
+                        if !text.is_shared() && seen_raw.insert(text.as_ptr()) {
+                            report.stored_bytes += text.len();
+                        }
+                    }
+                }
+
+                let text = match &field.val {
+                    FieldValue::String(text) => text,
+                    FieldValue::Formula(formula) => {
+                        report.stored_bytes += formula.len();
+                        continue;
+                    }
+                    _ => continue,
+                };
+
+                report.strings += 1;
+                report.string_bytes += text.len();
+                let first_copy = seen.insert(text.as_ptr());
+                if first_copy {
+                    report.distinct_strings += 1;
+                }
+                match text {
+                    // mapped strings live in the page cache rather than on the heap.
+                    Text::Shared { .. } => {}
+                    Text::Pooled(_) if !first_copy => {}
+                    _ => report.stored_bytes += text.len(),
+                }
             }
-            writer.write_all(&row_output)?;
-            writer.write(&[b'\n'])?;
         }
-        writer.flush();
+
+        report
+    }
+
+    // guesses the dialect from the start of the file, use from_path_with_dialect to set it explicitly.
+    // the file is memory mapped when it can be, pipes and the like are read in full.
+    pub fn from_path(path: &str) -> std::io::Result<Self> {
+        let source = Source::open(path)?;
+        let dialect = sniff(&source);
+        Ok(Self::from_source(source, dialect)?)
+    }
+
+    pub fn from_path_with_dialect(path: &str, dialect: Dialect) -> Result<Self, CsvError> {
+        Self::from_source(Source::open(path)?, dialect)
+    }
+
+    // reads each column the schema declares as its type instead of guessing cell by cell.
+    pub fn from_path_with_schema(path: &str, schema: &Schema) -> Result<Self, SchemaError> {
+        let mut excel = Self::from_path(path)?;
+        schema.apply(&mut excel)?;
+        Ok(excel)
+    }
+
+    // rows with invalid utf-8 are skipped like any other malformed row. when `quarantine` is given the
+    // raw bytes of every skipped row are written there so it can be fixed up and loaded separately.
+    pub fn from_path_lenient(path: &str, quarantine: Option<&str>) -> std::io::Result<(Self, Vec<Warning>)> {
+        let source = Source::open(path)?;
+        let dialect = sniff(&source);
+        let (excel, warnings) = Self::from_source_lenient(source, dialect)?;
+
+        if let Some(quarantine) = quarantine {
+            let mut writer = BufWriter::new(File::create(quarantine)?);
+            for warning in &warnings {
+                writer.write_all(&warning.raw)?;
+                if !warning.raw.ends_with(b"\n") && !warning.raw.ends_with(b"\r") {
+                    writer.write_all(dialect.line_terminator.as_str().as_bytes())?;
+                }
+            }
+            writer.flush()?;
+        }
+
+        Ok((excel, warnings))
+    }
+
+    // one sheet of an .xlsx workbook, the first unless `sheet` names another. see xlsx::read_sheets
+    // for what's kept and what isn't.
+    pub fn from_xlsx(path: &str, sheet: Option<&str>) -> Result<Self, XlsxError> {
+        let sheets = read_sheets(File::open(path)?)?;
+        let found = match sheet {
+            Some(name) => sheets.into_iter().find(|(other, _)| other.eq_ignore_ascii_case(name)),
+            None => sheets.into_iter().next(),
+        };
+        found
+            .map(|(_, excel)| excel)
+            .ok_or_else(|| XlsxError::UnknownSheet(sheet.unwrap_or_default().to_string()))
+    }
+
+    pub fn from_path_strict(path: &str) -> Result<Self, CsvError> {
+        let source = Source::open(path)?;
+        let dialect = sniff(&source);
+        Self::from_source_strict(source, dialect)
+    }
+
+    // writes the grid in its dialect, every row ends with the line terminator. reading the file back
+    // in the same dialect gives the same cells.
+    pub fn to_file(&self, path: &str) -> std::io::Result<()> {
+        self.to_file_with(path, OutputMode::Formulas)
+    }
+
+    // the same as to_file, with formulas written the way `mode` says.
+    pub fn to_file_with(&self, path: &str, mode: OutputMode) -> std::io::Result<()> {
+        self.write_with(File::create(path)?, mode)
+    }
+
+    // writes the grid to anything, like stdout or a Vec<u8>, the same way to_file writes a file. the
+    // output is buffered here so there's no need to wrap it in a BufWriter first.
+    pub fn write<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        self.write_with(writer, OutputMode::Formulas)
+    }
+
+    // the grid is worked out on its own, so in Values and Both formulas pointing at other sheets
+    // come out as #REF!. write_evaluated takes an evaluator that knows about the rest of the workbook.
+    pub fn write_with<W: Write>(&self, writer: W, mode: OutputMode) -> std::io::Result<()> {
+        self.write_evaluated(writer, mode, &mut Evaluator::new(self), 0)
+    }
+
+    // the same as write_with, with formulas worked out by `evaluator` as its numbered `sheet`.
+    pub fn write_evaluated<W: Write>(&self, writer: W, mode: OutputMode, evaluator: &mut Evaluator, sheet: usize) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(writer);
+        let mut delimiter = [0; 4];
+        let delimiter = self.dialect.delimiter.encode_utf8(&mut delimiter).as_bytes();
+
+        let formula_cols: HashSet<usize> = match mode {
+            OutputMode::Both => self
+                .rows
+                .iter()
+                .flat_map(|row| row.iter().enumerate())
+                .filter(|(_, field)| matches!(field.val, FieldValue::Formula(_)))
+                .map(|(col, _)| col)
+                .collect(),
+            _ => HashSet::new(),
+        };
+
+        for (row_idx, row) in self.rows.iter().enumerate() {
+            for (col, field) in row.iter().enumerate() {
+                if col > 0 {
+                    writer.write_all(delimiter)?;
+                }
+                let text = match (&field.val, mode) {
+                    (FieldValue::Formula(_), OutputMode::Values | OutputMode::Both) => {
+                        let value = evaluator.cell(sheet, CellAddress { row: row_idx, col });
+                        FieldValue::from(value).to_csv(&self.dialect)
+                    }
+                    _ => field.to_csv(&self.dialect),
+                };
+                writer.write_all(text.as_bytes())?;
+
+                // the formula next to its value, the column gets its own header like "total formula".
+                if formula_cols.contains(&col) {
+                    writer.write_all(delimiter)?;
+                    let formula = match &field.val {
+                        FieldValue::Formula(_) => field.to_csv(&self.dialect),
+                        _ if row_idx == 0 && self.dialect.has_header => {
+                            let header = format!("{} formula", field.text(&self.dialect));
+                            Value::String(&header).to_csv(&self.dialect)
+                        }
+                        _ => String::new(),
+                    };
+                    writer.write_all(formula.as_bytes())?;
+                }
+            }
+            writer.write_all(self.dialect.line_terminator.as_str().as_bytes())?;
+        }
+        writer.flush()?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+impl Grid for Excel {
+    fn value(&self, address: CellAddress) -> Option<Value<'_>> {
+        self.get(address).map(|field| field.val.as_value())
+    }
+
+    fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    fn column_count(&self) -> usize {
+        self.rows.iter().map(|row| row.len()).max().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+    use crate::csv::LineTerminator;
+
+    #[test]
+    fn test_strings_point_into_mapped_file() {
+        let mut excel = Excel::from_path("test_data/primitives.csv").unwrap();
+        assert!(matches!(excel.rows[0][0].val, FieldValue::Integer(123)));
+
+        let text = match &mut excel.rows[0][1].val {
+            FieldValue::String(text) => text,
+            val => panic!("expected a string, got {:?}", val),
+        };
+        assert!(text.is_shared());
+        assert_eq!(*text, "Hello, World");
+
+        // changing the text takes a copy, the rest of the grid still shares the file.
+        text.to_mut().push('!');
+        assert!(!text.is_shared());
+        assert_eq!(*text, "Hello, World!");
+        assert!(matches!(&excel.rows[1][1].val, FieldValue::String(text) if text.is_shared() && *text == "World"));
+
+        // nothing to point at when the grid is built from a string.
+        let excel = Excel::new("a,b\n".to_string());
+        assert!(matches!(&excel.rows[0][0].val, FieldValue::String(Text::Pooled(_))));
+    }
+
+    #[test]
+    fn test_repeated_strings_are_pooled() {
+        let excel = Excel::new("a,b\nb,a\na,\"b\"\n".to_string());
+        match (&excel.rows[0][0].val, &excel.rows[2][0].val) {
+            (FieldValue::String(Text::Pooled(first)), FieldValue::String(Text::Pooled(second))) => {
+                assert!(Arc::ptr_eq(first, second));
+            }
+            vals => panic!("expected pooled strings, got {:?}", vals),
+        }
+
+        let report = excel.memory_report();
+        assert_eq!(report.cells, 6);
+        assert_eq!(report.strings, 6);
+        assert_eq!(report.distinct_strings, 2);
+        assert_eq!((report.string_bytes, report.stored_bytes), (6, 2));
+
+        // the weather stations repeat on every row, most of medium.csv's text is only stored once.
+        let medium = std::fs::read_to_string("test_data/medium.csv").unwrap();
+        let report = Excel::new(medium).memory_report();
+        assert!(report.distinct_strings * 10 < report.strings, "{}", report);
+        assert!(report.stored_bytes * 10 < report.string_bytes, "{}", report);
+    }
+
+    #[test]
+    fn test_numbers_keep_their_precision() {
+        let excel = Excel::new("3000000000,2.71,-13.23,1301738\n".to_string());
+        assert!(matches!(excel.rows[0][0].val, FieldValue::Integer(3_000_000_000)));
+        assert!(matches!(excel.rows[0][1].val, FieldValue::Float(fl) if fl == 2.71));
+        let written: Vec<String> = excel.rows[0].iter().map(|field| field.val.to_string()).collect();
+        assert_eq!(written, vec!["3000000000", "2.71", "-13.23", "1301738"]);
+
+        // a float drops the trailing zero, an exact decimal keeps it.
+        let excel = Excel::new("-35.70,0.10\n".to_string());
+        assert_eq!(excel.rows[0][0].val.to_string(), "-35.7");
+
+        let dialect = Dialect { decimals: true, ..Dialect::default() };
+        let excel = Excel::with_dialect("-35.70,0.10,7,1e3\n".to_string(), dialect).unwrap();
+        assert!(matches!(excel.rows[0][0].val, FieldValue::Decimal(_)));
+        assert!(matches!(excel.rows[0][2].val, FieldValue::Integer(7)));
+        assert!(matches!(excel.rows[0][3].val, FieldValue::Float(_)));
+        let written: Vec<String> = excel.rows[0].iter().map(|field| field.val.to_string()).collect();
+        // 1e3 is a float, written so it reads back as one.
+        assert_eq!(written, vec!["-35.70", "0.10", "7", "1000.0"]);
+
+        // zero has no sign as a value, but the file is still written back the way it was.
+        let excel = Excel::with_dialect("-0.00,0.00\n".to_string(), dialect).unwrap();
+        assert_eq!(excel.rows[0][0].val.as_value(), excel.rows[0][1].val.as_value());
+        assert_eq!(excel.rows[0][0].to_csv(&dialect), "-0.00");
+    }
+
+    #[test]
+    fn test_quoted_numbers_stay_text() {
+        let src = "\"00123\",\"1e5\",00123,\"=1+1\"\n";
+        let excel = Excel::new(src.to_string());
+        assert!(matches!(&excel.rows[0][0].val, FieldValue::String(text) if text == "00123"));
+        assert!(matches!(&excel.rows[0][1].val, FieldValue::String(text) if text == "1e5"));
+        assert!(matches!(excel.rows[0][2].val, FieldValue::Integer(123)));
+        // quotes don't stop a formula being a formula.
+        assert!(matches!(excel.rows[0][3].val, FieldValue::Formula(_)));
+
+        let dialect = Dialect { quoted_numbers: true, ..Dialect::default() };
+        let excel = Excel::with_dialect(src.to_string(), dialect).unwrap();
+        assert!(matches!(excel.rows[0][0].val, FieldValue::Integer(123)));
+        assert!(matches!(excel.rows[0][1].val, FieldValue::Float(fl) if fl == 1e5));
+
+        // text that looks like a number is quoted when written so it's read back as text.
+        assert_eq!(FieldValue::String("00123".into()).to_csv(&Dialect::default()), "\"00123\"");
+        assert_eq!(FieldValue::String("00123".into()).to_csv(&dialect), "00123");
+    }
+
+    fn assert_same_grid(a: &Excel, b: &Excel) {
+        assert_eq!(a.row_count(), b.row_count());
+        for row in 0..a.row_count() {
+            assert_eq!(a.rows[row].len(), b.rows[row].len(), "row {}", row);
+            for col in 0..a.rows[row].len() {
+                let address = CellAddress { row, col };
+                assert_eq!(a.value(address), b.value(address), "{}", address);
+            }
+        }
+    }
+
+    #[test]
+    fn test_written_file_reads_back_the_same() {
+        let out = temp_path("write_round_trip.csv");
+        let path = out.to_str().unwrap();
+        let files = ["test_data/primitives.csv", "test_data/escapes_from_excel.csv", "test_data/formulas.csv", "test_data/medium.csv"];
+        for file in files {
+            for quoting in [Quoting::Minimal, Quoting::All, Quoting::NonNumeric, Quoting::Never] {
+                // with every field quoted, quotes can't say what's text.
+                let quoted_numbers = quoting == Quoting::All;
+                let dialect = Dialect { quoting, quoted_numbers, ..sniff(&std::fs::read(file).unwrap()) };
+                let excel = Excel::from_path_with_dialect(file, dialect).unwrap();
+                excel.to_file(path).unwrap();
+                let reread = Excel::from_path_with_dialect(path, dialect).unwrap();
+                assert_same_grid(&excel, &reread);
+            }
+        }
+        let _ = std::fs::remove_file(&out);
+
+        // no trailing delimiter or blank last line, and text, blanks and numbers are told apart.
+        let src = "a,,\"00123\",1.50,\"x,y\"\r\n";
+        let expected = [
+            (Quoting::Minimal, "a,,\"00123\",1.50,\"x,y\"\r\n"),
+            (Quoting::All, "\"a\",,\"00123\",\"1.5\",\"x,y\"\r\n"),
+            (Quoting::NonNumeric, "\"a\",,\"00123\",1.5,\"x,y\"\r\n"),
+            (Quoting::Never, "a,,\"00123\",1.5,\"x,y\"\r\n"),
+        ];
+        for (quoting, expected) in expected {
+            let dialect = Dialect { quoting, line_terminator: LineTerminator::CRLF, ..Dialect::default() };
+            let excel = Excel::with_dialect(src.to_string(), Dialect { quoting: Quoting::Minimal, ..dialect }).unwrap();
+            let mut written = Vec::new();
+            Excel { dialect, ..excel }.write(&mut written).unwrap();
+            assert_eq!(String::from_utf8(written).unwrap(), expected, "{:?}", quoting);
+        }
+    }
+
+    #[test]
+    fn test_output_modes() {
+        let src = "price,qty,total,note\n1.5,2,=A2*B2,x\n3,,\"=A3*B3\",=1/0\n";
+        let dialect = Dialect { has_header: true, ..Dialect::default() };
+        let excel = Excel::with_dialect(src.to_string(), dialect).unwrap();
+
+        let expected = [
+            (OutputMode::Formulas, src),
+            (OutputMode::Values, "price,qty,total,note\n1.5,2,3,x\n3,,0,#DIV/0!\n"),
+            (
+                OutputMode::Both,
+                "price,qty,total,total formula,note,note formula\n1.5,2,3,=A2*B2,x,\n3,,0,\"=A3*B3\",#DIV/0!,=1/0\n",
+            ),
+        ];
+        for (mode, expected) in expected {
+            let mut written = Vec::new();
+            excel.write_with(&mut written, mode).unwrap();
+            assert_eq!(String::from_utf8(written).unwrap(), expected, "{:?}", mode);
+        }
+
+        // values read back as plain cells.
+        let mut written = Vec::new();
+        excel.write_with(&mut written, OutputMode::Values).unwrap();
+        let reread = Excel::from_bytes(&written, dialect).unwrap();
+        assert!(matches!(reread.rows[1][2].val, FieldValue::Integer(3)));
+        assert!(matches!(&reread.rows[2][3].val, FieldValue::String(text) if *text == "#DIV/0!"));
+    }
+
+    #[test]
+    fn test_blank_cells() {
+        let src = "a,,\"\",1\n";
+        let excel = Excel::new(src.to_string());
+        assert!(matches!(excel.rows[0][1].val, FieldValue::Empty));
+        assert!(matches!(excel.rows[0][2].val, FieldValue::Empty));
+        assert_eq!(excel.value(CellAddress::parse("B1").unwrap()).unwrap().as_operand(), Some(0.0));
+        assert_eq!(excel.column_sum(1), 0.0);
+
+        let dialect = Dialect { empty_strings: true, ..Dialect::default() };
+        let excel = Excel::with_dialect(src.to_string(), dialect).unwrap();
+        assert!(matches!(excel.rows[0][1].val, FieldValue::Empty));
+        assert!(matches!(&excel.rows[0][2].val, FieldValue::String(text) if text.is_empty()));
+        assert_eq!(excel.value(CellAddress::parse("C1").unwrap()).unwrap().as_operand(), None);
+
+        // either way they're written back as they were read.
+        let written: Vec<String> = excel.rows[0].iter().map(|field| field.to_csv(&dialect)).collect();
+        assert_eq!(written, vec!["a", "", "\"\"", "1"]);
+        assert_eq!(FieldValue::Empty.to_csv(&dialect), "");
+        assert_eq!(FieldValue::String("".into()).to_csv(&dialect), "\"\"");
+    }
+
+    #[test]
+    fn test_unchanged_fields_keep_their_text() {
+        for dialect in [Dialect::default(), Dialect { quoted_numbers: true, ..Dialect::default() }] {
+            for path in ["test_data/primitives.csv", "test_data/escapes_from_excel.csv", "test_data/medium.csv"] {
+                let src = std::fs::read_to_string(path).unwrap();
+                let excel = Excel::from_path_with_dialect(path, dialect).unwrap();
+                let lines: Vec<String> = excel
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        let fields: Vec<String> = row.iter().map(|field| field.to_csv(&dialect)).collect();
+                        fields.join(",")
+                    })
+                    .collect();
+                assert_eq!(lines.join("\n"), src.replace("\r\n", "\n").trim_end_matches('\n'), "{}", path);
+            }
+        }
+
+        let src = "-35.70,\"abc\",1e5,\"a\"\"b\",+7,x\n";
+        let mut excel = Excel::new(src.to_string());
+        let written: Vec<String> = excel.rows[0].iter().map(|field| field.to_csv(&excel.dialect)).collect();
+        assert_eq!(written.join(",") + "\n", src);
+
+        // a changed value is written from scratch instead of with text that no longer matches it.
+        excel.rows[0][0].val = FieldValue::Float(-1.5);
+        excel.rows[0][1].val = FieldValue::Integer(4);
+        excel.rows[0][4].val = FieldValue::Integer(7);
+        if let FieldValue::String(text) = &mut excel.rows[0][5].val {
+            text.to_mut().push_str(",y");
+        }
+        let written: Vec<String> = excel.rows[0].iter().map(|field| field.to_csv(&excel.dialect)).collect();
+        assert_eq!(written, vec!["-1.5", "4", "1e5", "\"a\"\"b\"", "+7", "\"x,y\""]);
+        assert_eq!(Field::new(FieldValue::Float(-35.7)).to_csv(&excel.dialect), "-35.7");
+
+        // the text is kept through a change of delimiter, unless it would read differently.
+        let dialect = Dialect { trim: true, ..Dialect::default() };
+        let excel = Excel::with_dialect("1e5, 7 ,\"2\"\n".to_string(), dialect).unwrap();
+        let semicolons = Dialect { delimiter: ';', ..dialect };
+        let written: Vec<String> = excel.rows[0].iter().map(|field| field.to_csv(&semicolons)).collect();
+        assert_eq!(written, vec!["1e5", " 7 ", "\"2\""]);
+        let untrimmed = Dialect { trim: false, ..semicolons };
+        assert_eq!(excel.rows[0][1].to_csv(&untrimmed), "7");
+        assert_eq!(excel.rows[0][0].text(&untrimmed), "1e5");
+    }
+}
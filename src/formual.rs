@@ -1,4 +1,5 @@
 use std::ops::{ Sub, Mul, Div, Add };
+use std::cell::Cell;
 use std::collections::HashSet;
 use std::fmt;
 /*
@@ -10,10 +11,12 @@ enum Token <'a> {
     OpenBracket, // '('
     CloseBracket, // ')'
     RangeDelimiter, // ':'
-    TextQualifier, // '"'
+    Literal(&'a [char]), // the raw contents of a string like "it's", "" is a quote inside it
     Comma, // ',
+    SheetDelimiter, // '!'
     Operator(char), // '+', '-', etc..
     Text(&'a [char]), // any purly text field
+    SheetName(&'a [char]), // the raw contents of a quoted sheet name like 'My Sheet'
     EndOfFile,
 }
 
@@ -24,10 +27,12 @@ impl<'a> PartialEq for Token<'a> {
             (OpenBracket, OpenBracket) => true,
             (CloseBracket, CloseBracket) => true,
             (RangeDelimiter, RangeDelimiter) => true,
-            (TextQualifier, TextQualifier) => true,
             (Comma, Comma) => true,
+            (SheetDelimiter, SheetDelimiter) => true,
             (EndOfFile, EndOfFile) => true,
             (Text(a), Text(b)) => a == b,
+            (SheetName(a), SheetName(b)) => a == b,
+            (Literal(a), Literal(b)) => a == b,
             (Operator(a), Operator(b)) => a == b,
             _ => false,
         }
@@ -53,7 +58,7 @@ impl<'a> Tokenizer<'a> {
     }
 
     fn peek(&self) -> Option<&char> {
-        self.view.get(0)
+        self.view.first()
     }
 
     fn next_is(&self, cmp: char) -> bool {
@@ -79,20 +84,13 @@ impl<'a> Tokenizer<'a> {
     }
 
     fn lookahead(&mut self, n: usize) -> Token<'a> {
-        let original = &self.view[0..];
-        if n <= 1 {
-            let mut toke = self.next();
-            self.view = &original;
-            return toke;
-        } else {
-            let mut toke = self.next();
-            for i in 1..n {
-                toke = self.next();
-            }
-            self.view = &original;
-            return toke
-
+        let original = self.view;
+        let mut toke = self.next();
+        for _ in 1..n {
+            toke = self.next();
         }
+        self.view = original;
+        toke
     }
 
     fn next(&mut self) -> Token<'a> {
@@ -123,9 +121,24 @@ impl<'a> Tokenizer<'a> {
                     return Token::RangeDelimiter
                 }
 
+                // a string is one token, so the spaces, quotes and !s inside it are kept as they are
+                // rather than read as formula syntax. one with no closing quote runs to the end.
                 '"' => {
                     self.skip(1);
-                    return Token::TextQualifier
+                    let tmp = self.view;
+                    let mut idx = 0;
+                    while !self.empty() {
+                        if self.next_is('"') {
+                            self.skip(1);
+                            if !self.next_is('"') {
+                                return Token::Literal(&tmp[0..idx]);
+                            }
+                            idx += 1;
+                        }
+                        self.skip(1);
+                        idx += 1;
+                    }
+                    return Token::EndOfFile;
                 }
 
                 ',' => {
@@ -133,6 +146,30 @@ impl<'a> Tokenizer<'a> {
                     return Token::Comma
                 }
 
+                '!' => {
+                    self.skip(1);
+                    return Token::SheetDelimiter
+                }
+
+                // quoted sheet names escape a single quote by doubling it, e.g. 'Bob''s Sheet'
+                '\'' => {
+                    self.skip(1);
+                    let tmp = self.view;
+                    let mut idx = 0;
+                    while !self.empty() {
+                        if self.next_is('\'') {
+                            self.skip(1);
+                            if !self.next_is('\'') {
+                                break;
+                            }
+                            idx += 1;
+                        }
+                        self.skip(1);
+                        idx += 1;
+                    }
+                    return Token::SheetName(&tmp[0..idx]);
+                }
+
                 ' ' => {
                     self.skip(1);
                     return self.next()
@@ -141,7 +178,7 @@ impl<'a> Tokenizer<'a> {
                 _ => {
                     let mut idx = 0;
                     let tmp = &self.view[0..];
-                    let sp_c = ['+', '-', '/', '*', ':', '(', ')', '"', ',', ' '];
+                    let sp_c = ['+', '-', '/', '*', ':', '(', ')', '"', ',', ' ', '!', '\''];
                     while !self.next_is_one_of(&sp_c) && !self.empty() {
                         self.skip(1);
                         idx += 1;
@@ -157,7 +194,7 @@ impl<'a> Tokenizer<'a> {
 }
 
 #[derive(Debug)]
-enum Operator {
+pub enum Operator {
     Addition,
     Subtraction,
     Division,
@@ -166,7 +203,7 @@ enum Operator {
 }
 
 impl Operator {
-    fn new(op: char) -> Self {
+    pub fn new(op: char) -> Self {
         match op {
             '+' => Operator::Addition,
             '-' => Operator::Subtraction,
@@ -176,7 +213,7 @@ impl Operator {
         }
     }
 
-    fn apply<T>(&self, lhs: T, rhs: T) -> Option<T> 
+    pub fn apply<T>(&self, lhs: T, rhs: T) -> Option<T> 
     where
         T: Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Div<Output = T>
     {
@@ -189,37 +226,25 @@ impl Operator {
         }
     }
 
-    fn is_addition(&self) -> bool {
-        match self {
-            Operator::Addition => true,
-            _ => false
-        }
+    pub fn is_addition(&self) -> bool {
+        matches!(self, Operator::Addition)
     }
 
-    fn is_subtraction(&self) -> bool {
-        match self {
-            Operator::Subtraction => true,
-            _ => false
-        }
+    pub fn is_subtraction(&self) -> bool {
+        matches!(self, Operator::Subtraction)
     }
 
-    fn is_division(&self) -> bool {
-        match self {
-            Operator::Division => true,
-            _ => false
-        }
+    pub fn is_division(&self) -> bool {
+        matches!(self, Operator::Division)
     }
 
-    fn is_multiplication(&self) -> bool {
-        match self {
-            Operator::Multiplication => true,
-            _ => false
-        }
+    pub fn is_multiplication(&self) -> bool {
+        matches!(self, Operator::Multiplication)
     }
 }
 
 #[derive(Debug)]
-enum Node {
+pub enum Node {
     // top level entry point of the ast
     Formula {
         expr: Box<Node>,
@@ -250,6 +275,7 @@ enum Node {
     Primitive(String), // prims like string boolean int or float.
     CellRef(String), // a cell reference with (col, row) as strings.
     CellRange(Box<Node>, Box<Node>), // two cell refs.
+    SheetRef(String, Box<Node>), // a cell ref or range on another sheet, like Sheet2!A1.
     // a function call.
     Function {
         name: String,
@@ -258,10 +284,11 @@ enum Node {
 }
 
 #[derive(Debug, Clone)]
-enum ParseError {
+pub enum ParseError {
     UnexpectedEndOfFile,
     UnexpectedToken { expected: String, found: String },
     InvalidExpression,
+    TooDeep, // more than MAX_NESTING brackets, minus signs or function calls inside one another.
     // Add more error types as needed
 }
 
@@ -273,6 +300,7 @@ impl fmt::Display for ParseError {
                 write!(f, "Expected token '{}', but found '{}'", expected, found)
             }
             ParseError::InvalidExpression => write!(f, "Invalid expression"),
+            ParseError::TooDeep => write!(f, "Formula is nested more than {} deep", MAX_NESTING),
             // Handle other errors here
         }
     }
@@ -280,22 +308,28 @@ impl fmt::Display for ParseError {
 
 pub type ParseResult = Result<Node, ParseError>;
 
+// each level is a few calls deep in the parser, this keeps well clear of the end of the stack. excel
+// itself stops at 64 functions inside one another.
+pub const MAX_NESTING: usize = 100;
+
 pub struct Ast<'a> {
     src: &'a str,
-    func_names: HashSet<String>
+    func_names: &'a HashSet<String>,
+    depth: Cell<usize>,
 }
 
 impl<'a> Ast<'a> {
-    fn new(src: &'a str, func_names: HashSet<String>) -> Self {
+    pub fn new(src: &'a str, func_names: &'a HashSet<String>) -> Self {
         Self {
             src,
-            func_names
+            func_names,
+            depth: Cell::new(0),
         }
     }
 
-    fn parse(&self) -> ParseResult {
+    pub fn parse(&self) -> ParseResult {
         let chars: Vec<char> = self.src.chars().collect();
-        if let Some(c) = chars.get(0) {
+        if let Some(c) = chars.first() {
             if *c == '=' {
                 let mut tokenizer = Tokenizer::new(&chars[1..]);
                 let expr = self.parse_expression(&mut tokenizer)?;
@@ -334,75 +368,117 @@ impl<'a> Ast<'a> {
         }
     }
 
+    // a chain like a - b + c is read left to right, each operator takes everything before it as
+    // its left hand side so it works out as (a - b) + c.
     fn parse_term(&self, tokenizer: &mut Tokenizer) -> ParseResult {
-        let lhs = self.parse_factor(tokenizer)?;
-        
-        match tokenizer.lookahead(1) {
-            Token::Operator(op) if op == '+' || op == '-' => {
-                let operator = Operator::new(op);
-                let _ = tokenizer.next();
-                let rhs = self.parse_factor(tokenizer).unwrap();
-
-                Ok(Node::Term {
-                    lhs: Box::new(lhs),
-                    op: Some(operator),
-                    rhs: Some(Box::new(rhs))
-                })
-            }
-
-            _ => {
-                Ok(Node::Term {
-                    lhs: Box::new(lhs),
-                    op: None,
-                    rhs: None,
-                })
-            }
+        let mut term = Node::Term {
+            lhs: Box::new(self.parse_factor(tokenizer)?),
+            op: None,
+            rhs: None,
+        };
+
+        loop {
+            let op = match tokenizer.lookahead(1) {
+                Token::Operator(op) if op == '+' || op == '-' => op,
+                _ => return Ok(term),
+            };
+            let _ = tokenizer.next();
+            let rhs = self.parse_factor(tokenizer)?;
+            let lhs = match term {
+                Node::Term { lhs, op: None, .. } => lhs,
+                term => Box::new(term),
+            };
+            term = Node::Term {
+                lhs,
+                op: Some(Operator::new(op)),
+                rhs: Some(Box::new(rhs)),
+            };
         }
     }
 
+    // same as terms, a / b * c is (a / b) * c.
     fn parse_factor(&self, tokenizer: &mut Tokenizer) -> ParseResult {
-        let lhs = self.parse_primary(tokenizer)?;
-        
-        match tokenizer.lookahead(1) {
-            Token::Operator(op) if op == '/' || op == '*' => {
-                let operator = Operator::new(op);
-                let _ = tokenizer.next();
-                let rhs = self.parse_primary(tokenizer)?;
-
-                Ok(Node::Factor {
-                    lhs: Box::new(lhs),
-                    op: Some(operator),
-                    rhs: Some(Box::new(rhs))
-                })
-            }
-
-            _ => {
-                Ok(Node::Factor {
-                    lhs: Box::new(lhs),
-                    op: None,
-                    rhs: None,
-                })
-            }
+        let mut factor = Node::Factor {
+            lhs: Box::new(self.parse_primary(tokenizer)?),
+            op: None,
+            rhs: None,
+        };
+
+        loop {
+            let op = match tokenizer.lookahead(1) {
+                Token::Operator(op) if op == '/' || op == '*' => op,
+                _ => return Ok(factor),
+            };
+            let _ = tokenizer.next();
+            let rhs = self.parse_primary(tokenizer)?;
+            let lhs = match factor {
+                Node::Factor { lhs, op: None, .. } => lhs,
+                factor => Box::new(factor),
+            };
+            factor = Node::Factor {
+                lhs,
+                op: Some(Operator::new(op)),
+                rhs: Some(Box::new(rhs)),
+            };
         }
     }
 
+    // brackets, minus signs and function arguments all come back through here, so this is where the
+    // nesting is counted.
     fn parse_primary(&self, tokenizer: &mut Tokenizer) -> ParseResult {
+        if self.depth.get() == MAX_NESTING {
+            return Err(ParseError::TooDeep);
+        }
+        self.depth.set(self.depth.get() + 1);
+        let node = self.parse_value(tokenizer);
+        self.depth.set(self.depth.get() - 1);
+        node
+    }
+
+    fn parse_value(&self, tokenizer: &mut Tokenizer) -> ParseResult {
         match tokenizer.next() {
             Token::Text(text) => {
                 let as_string: String = text.iter().collect();
                 
-                // Check if the text is a numeric, boolean, cell reference, or a string literal
-                if self.is_numeric(text) || self.is_boolean(text) {
+                // a sheet name can look like a cell ref (Sheet2), so check for the '!' first.
+                if tokenizer.lookahead(1) == Token::SheetDelimiter {
+                    let _ = tokenizer.next();
+                    self.parse_sheet_ref(tokenizer, as_string)
+                } else if self.is_numeric(text) || self.is_boolean(text) {
+                    // Check if the text is a numeric, boolean, cell reference, or a string literal
                     Ok(Node::Primitive(as_string))
                 } else if self.is_cell_ref(text) {
-                    Ok(Node::CellRef(as_string))
+                    self.parse_range(tokenizer, self.cell_ref(text))
                 } else {
                     self.parse_function(tokenizer, as_string)
                 }
             },
 
-            Token::TextQualifier => {
-                return self.parse_string(tokenizer);
+            Token::SheetName(name) => {
+                let as_string: String = name.iter().collect();
+                let next_token = tokenizer.next();
+                if next_token != Token::SheetDelimiter {
+                    return Err(ParseError::UnexpectedToken {
+                        expected: "SheetDelimiter".to_string(),
+                        found: format!("{:?}", next_token),
+                    });
+                }
+                self.parse_sheet_ref(tokenizer, as_string.replace("''", "'"))
+            }
+
+            Token::Literal(text) => {
+                let as_string: String = text.iter().collect();
+                Ok(Node::Primitive(as_string.replace("\"\"", "\"")))
+            }
+
+            // a minus sign in front of a value, like -A1, is the same as multiplying it by -1.
+            Token::Operator('-') => {
+                let operand = self.parse_primary(tokenizer)?;
+                Ok(Node::Factor {
+                    lhs: Box::new(Node::Primitive("-1".to_string())),
+                    op: Some(Operator::Multiplication),
+                    rhs: Some(Box::new(operand)),
+                })
             }
 
             Token::OpenBracket => {
@@ -417,43 +493,42 @@ impl<'a> Ast<'a> {
         }
     }
 
-    fn parse_string(&self, tokenizer: &mut Tokenizer) -> ParseResult {
-        let mut string = String::new();
-        let mut next_token = tokenizer.next();
-        let mut done = false;
-        
-        while !done {
-            match next_token {
-                Token::TextQualifier => {
-                    if tokenizer.lookahead(1) == Token::TextQualifier {
-                        string.push('"');
-                        let _ = tokenizer.next();
-                    } else {
-                        done = true;
-                    }
-                }
+    // the reference after a "Sheet!" prefix, either a single cell or a range of cells.
+    fn parse_sheet_ref(&self, tokenizer: &mut Tokenizer, sheet: String) -> ParseResult {
+        match tokenizer.next() {
+            Token::Text(text) if self.is_cell_ref(text) => {
+                let cell = self.cell_ref(text);
+                let reference = self.parse_range(tokenizer, cell)?;
+                Ok(Node::SheetRef(sheet, Box::new(reference)))
+            }
 
-                Token::Text(text) => {
-                    let as_string: String = text.iter().collect();
-                    string.push_str(&as_string);
-                }
+            token => Err(ParseError::UnexpectedToken {
+                expected: "CellRef".to_string(),
+                found: format!("{:?}", token),
+            }),
+        }
+    }
 
-                Token::EndOfFile => {
-                    return Err(ParseError::UnexpectedEndOfFile);
-                }
+    // turns "A1" into a range if it is followed by ":B2", otherwise hands back the cell ref as is.
+    fn parse_range(&self, tokenizer: &mut Tokenizer, start: Node) -> ParseResult {
+        if tokenizer.lookahead(1) != Token::RangeDelimiter {
+            return Ok(start);
+        }
 
-                _ => {
-                    return Err(ParseError::InvalidExpression);
-                }
+        let _ = tokenizer.next();
+        match tokenizer.next() {
+            Token::Text(text) if self.is_cell_ref(text) => {
+                let end = self.cell_ref(text);
+                Ok(Node::CellRange(Box::new(start), Box::new(end)))
             }
 
-            next_token = tokenizer.next();
+            token => Err(ParseError::UnexpectedToken {
+                expected: "CellRef".to_string(),
+                found: format!("{:?}", token),
+            }),
         }
-        
-        Ok(Node::Primitive(string))
     }
 
-    // for now we will only support functions that take n arguments, no cell ranges.
     fn parse_function(&self, tokenizer: &mut Tokenizer, name: String) -> ParseResult {
         // Check if the name is a valid function name
         if !self.func_names.contains(&name) {
@@ -488,13 +563,18 @@ impl<'a> Ast<'a> {
         })
     }
 
+    // a column and row can each be fixed with a $, like $A$1, which only matters when a formula is
+    // copied somewhere else.
     fn is_cell_ref(&self, chars: &'a [char]) -> bool {
         let mut has_column = false;
         let mut has_row = false;
+        let mut fixed_row = false;
     
-        for &c in chars {
+        for (idx, &c) in chars.iter().enumerate() {
             match c {
-                'A'..='Z' | 'a'..='z' if !has_row => has_column = true,
+                '$' if idx == 0 => {}
+                '$' if has_column && !has_row && !fixed_row => fixed_row = true,
+                'A'..='Z' | 'a'..='z' if !has_row && !fixed_row => has_column = true,
                 '0'..='9' if has_column => has_row = true,
                 _ => return false,  // Invalid character or sequence
             }
@@ -503,22 +583,20 @@ impl<'a> Ast<'a> {
         has_column && has_row  // Must have both column and row parts
     }
 
-    fn is_numeric(&self, chars: &'a [char]) -> bool {
-        for c in chars {
-            if !c.is_numeric() {
-                return false
-            }
-        }
-        true
+    // the $s are left out, "$A$1" is the same cell as "A1".
+    fn cell_ref(&self, chars: &'a [char]) -> Node {
+        Node::CellRef(chars.iter().filter(|c| **c != '$').collect())
     }
 
-    fn is_string_literal(&self, chars: &'a [char]) -> bool {
-        return chars.len() > 0 && chars[0] == '"'
+    // digits with at most one decimal point, like 12 or 3.5.
+    fn is_numeric(&self, chars: &'a [char]) -> bool {
+        let points = chars.iter().filter(|c| **c == '.').count();
+        points <= 1 && points < chars.len() && chars.iter().all(|c| c.is_ascii_digit() || *c == '.')
     }
 
     fn is_boolean(&self, chars: &'a [char]) -> bool {
         let as_string: String = chars.iter().collect();
-        return as_string == "TRUE" || as_string == "FALSE"
+        as_string == "TRUE" || as_string == "FALSE"
     }
 
     fn expect(&self, tokenizer: &mut Tokenizer, expected: Token, result: Node) -> ParseResult {
@@ -607,10 +685,77 @@ mod tests {
         func_names.insert("NOT".to_string());
         func_names.insert("OR".to_string());
         func_names.insert("GREATER".to_string());
-        let mut parser = Ast::new(&input, func_names);
+        let parser = Ast::new(&input, &func_names);
         let ast = parser.parse().unwrap();
         println!("{:#?}", ast);
     }
+
+    #[test]
+    fn test_sheet_tokens() {
+        let input = "Sheet2!A1+'Bob''s Sheet'!B2".chars().collect::<Vec<_>>();
+        let mut tokenizer = Tokenizer::new(&input);
+        assert_eq!(tokenizer.next(), Token::Text(&['S', 'h', 'e', 'e', 't', '2']));
+        assert_eq!(tokenizer.next(), Token::SheetDelimiter);
+        assert_eq!(tokenizer.next(), Token::Text(&['A', '1']));
+        assert_eq!(tokenizer.next(), Token::Operator('+'));
+        assert_eq!(tokenizer.next(), Token::SheetName(&input[11..23]));
+        assert_eq!(tokenizer.next(), Token::SheetDelimiter);
+        assert_eq!(tokenizer.next(), Token::Text(&['B', '2']));
+        assert_eq!(tokenizer.next(), Token::EndOfFile);
+    }
+
+    #[test]
+    fn test_literal_tokens() {
+        // quotes, !s and spaces inside a string aren't sheet syntax.
+        let input = "\"it's\"&\"Hello! \"\"a b\"\"\"".chars().collect::<Vec<_>>();
+        let mut tokenizer = Tokenizer::new(&input);
+        assert_eq!(tokenizer.next(), Token::Literal(&input[1..5]));
+        assert_eq!(tokenizer.next(), Token::Text(&['&']));
+        assert_eq!(tokenizer.next(), Token::Literal(&input[8..22]));
+        assert_eq!(tokenizer.next(), Token::EndOfFile);
+
+        let input = "\"open".chars().collect::<Vec<_>>();
+        assert_eq!(Tokenizer::new(&input).next(), Token::EndOfFile);
+    }
+
+    #[test]
+    fn test_parser_literals() {
+        let func_names = HashSet::new();
+        for (input, text) in [("=\"it's\"", "it's"), ("=\"Hello!\"", "Hello!"), ("=\"a \"\"b\"\" c\"", "a \"b\" c")] {
+            let parser = Ast::new(input, &func_names);
+            let ast = format!("{:?}", parser.parse().unwrap());
+            assert!(ast.contains(&format!("Primitive({:?})", text)), "{}", ast);
+        }
+        assert!(Ast::new("=\"open", &func_names).parse().is_err());
+    }
+
+    #[test]
+    fn test_parser_sheet_refs() {
+        let input = "=SUM('My Sheet'!A1:B9, Sheet2!C3)".to_string();
+        let mut func_names = HashSet::new();
+        func_names.insert("SUM".to_string());
+        let parser = Ast::new(&input, &func_names);
+        let ast = format!("{:?}", parser.parse().unwrap());
+        assert!(ast.contains(r#"SheetRef("My Sheet", CellRange(CellRef("A1"), CellRef("B9")))"#));
+        assert!(ast.contains(r#"SheetRef("Sheet2", CellRef("C3"))"#));
+
+        let input = "=SUM($A$1:B$2, Sheet2!$C3)".to_string();
+        let parser = Ast::new(&input, &func_names);
+        let ast = format!("{:?}", parser.parse().unwrap());
+        assert!(ast.contains(r#"CellRange(CellRef("A1"), CellRef("B2"))"#));
+        assert!(ast.contains(r#"SheetRef("Sheet2", CellRef("C3"))"#));
+        assert!(Ast::new("=A$$1", &func_names).parse().is_err());
+    }
+
+    #[test]
+    fn test_nesting() {
+        let func_names = HashSet::from(["SUM".to_string()]);
+        // a minus sign and a bracket are a level each, and so is the 1 in the middle.
+        let nested = |depth: usize| format!("=SUM({}1{})", "-(".repeat(depth / 2), ")".repeat(depth / 2));
+        assert!(Ast::new(&nested(MAX_NESTING - 2), &func_names).parse().is_ok());
+        assert!(matches!(Ast::new(&nested(MAX_NESTING), &func_names).parse(), Err(ParseError::TooDeep)));
+        assert!(matches!(Ast::new(&nested(100_000), &func_names).parse(), Err(ParseError::TooDeep)));
+    }
     // Additional tests for other scenarios (TextQualifier, RangeDelimiter, etc.)
 }
 
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// keeps one copy of each distinct string and hands out small ids for them. columns full of the same
+// few values, like a station name or a flag, only store the text once.
+#[derive(Debug, Default)]
+pub struct Interner {
+    strings: Vec<Arc<str>>,
+    ids: HashMap<Arc<str>, u32>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, text: &str) -> u32 {
+        if let Some(id) = self.ids.get(text) {
+            return *id;
+        }
+
+        let id = self.strings.len() as u32;
+        let text: Arc<str> = Arc::from(text);
+        self.strings.push(Arc::clone(&text));
+        self.ids.insert(text, id);
+        id
+    }
+
+    // the pooled copy itself, for keeping hold of outside of the interner.
+    pub fn intern_shared(&mut self, text: &str) -> Arc<str> {
+        let id = self.intern(text);
+        Arc::clone(&self.strings[id as usize])
+    }
+
+    pub fn get(&self, id: u32) -> &str {
+        &self.strings[id as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let mut interner = Interner::new();
+        let a = interner.intern("a");
+        let b = interner.intern("b");
+        assert_ne!(a, b);
+        assert_eq!(interner.intern("a"), a);
+        assert_eq!(interner.get(b), "b");
+        assert_eq!(interner.len(), 2);
+
+        let shared = interner.intern_shared("b");
+        assert!(Arc::ptr_eq(&shared, &interner.intern_shared("b")));
+        assert_eq!(interner.len(), 2);
+    }
+}
@@ -1,3 +1,17 @@
+pub mod cli;
+pub mod columnar;
 pub mod csv;
+pub mod date;
+pub mod decimal;
+pub mod eval;
 pub mod excel;
-pub mod formual;
\ No newline at end of file
+pub mod formual;
+pub mod interner;
+pub mod repl;
+pub mod schema;
+pub mod source;
+#[cfg(test)]
+mod test_util;
+pub mod view;
+pub mod workbook;
+pub mod xlsx;
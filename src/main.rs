@@ -1,30 +1,388 @@
-use std::env::{args};
-use excel_takehome::excel::Excel;
+use std::env::{args, var_os};
+use std::fs::{read_to_string, File};
+use std::io::{stdin, stdout, BufRead, ErrorKind, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use excel_takehome::cli::{parse_args, Args, Command, Format, Invocation, USAGE};
+use excel_takehome::csv::{sniff, Dialect};
+use excel_takehome::eval::{Computed, Evaluator};
+use excel_takehome::excel::{CellAddress, Excel, FieldValue, Grid};
+use excel_takehome::repl::{Reply, Session};
+use excel_takehome::schema::{Schema, SchemaError};
+use excel_takehome::source::Source;
+use excel_takehome::view::View;
+use excel_takehome::workbook::Workbook;
+use excel_takehome::xlsx::XlsxError;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
 
-fn parse_arguments() -> Result<String, &'static str> {
-    let usage = "Missing argumnets. Usage cargo run -- [file path]";
-    let mut args = args();
-    // skip execuable name...
-    args.next();
-    // parse the first argument as the file path.
-    if let Some(file_path) = args.next() {
-        return Ok(file_path);
+// how many rows stats looks at to guess the column types.
+const INFER_ROWS: usize = 1000;
+
+// exit codes, validate uses 1 for a file with problems in it and get for a cell with an error.
+const INVALID: u8 = 1;
+const FAILED: u8 = 2;
+
+fn main() -> ExitCode {
+    let args = match parse_args(args().skip(1)) {
+        Ok(Invocation::Run(args)) => args,
+        Ok(Invocation::Help(help)) => {
+            let _ = writeln!(stdout(), "{}", help);
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            return ExitCode::from(FAILED);
+        }
+    };
+
+    let result = match args.command {
+        Command::Convert | Command::Eval => convert(&args),
+        Command::Validate => validate(&args),
+        Command::Stats => stats(&args),
+        Command::Get => get(&args),
+        Command::Repl => repl(&args),
+        Command::View => view(&args),
+    };
+    match result {
+        Ok(code) => code,
+        // the reader went away, like piping into head, which isn't a failure.
+        Err(Failure::Io(err)) if err.kind() == ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(failure) => {
+            eprintln!("{}", failure);
+            ExitCode::from(FAILED)
+        }
+    }
+}
+
+enum Failure {
+    Io(std::io::Error),
+    Message(String),
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Failure::Io(err) => write!(f, "{}", err),
+            Failure::Message(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<std::io::Error> for Failure {
+    fn from(err: std::io::Error) -> Self {
+        Failure::Io(err)
+    }
+}
+
+// the whole input, from stdin when it's "-". files are mapped rather than read.
+fn open(input: &str) -> Result<Source, Failure> {
+    let source = if input == "-" {
+        Source::from_reader(stdin().lock())
+    } else {
+        Source::open(input)
+    };
+    source.map_err(|err| Failure::Message(format!("{}: {}", input, err)))
+}
+
+// sniffed from the input, --header can only say there's a header the sniffer missed.
+fn read_dialect(args: &Args, source: &Source) -> Dialect {
+    let dialect = sniff(source);
+    Dialect {
+        has_header: args.header || dialect.has_header,
+        ..dialect
+    }
+}
+
+fn is_xlsx(input: &str) -> bool {
+    Path::new(input).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("xlsx"))
+}
+
+// the input and the number of the sheet in it to work on. an .xlsx file keeps all of its sheets so
+// formulas can point from one to another, csv input is a workbook of one sheet read in the dialect
+// it's sniffed as.
+fn load(args: &Args) -> Result<(Workbook, usize), Failure> {
+    let failed = |err: &dyn std::fmt::Display| Failure::Message(format!("{}: {}", args.input, err));
+    if !is_xlsx(&args.input) {
+        if args.sheet.is_some() {
+            return Err(Failure::Message("--sheet only works with .xlsx files".to_string()));
+        }
+        let source = open(&args.input)?;
+        let dialect = read_dialect(args, &source);
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("", Excel::from_source(source, dialect).map_err(|err| failed(&err))?);
+        return Ok((workbook, 0));
+    }
+
+    let mut workbook = Workbook::from_xlsx(&args.input).map_err(|err| failed(&err))?;
+    let sheet = match args.sheet.as_deref() {
+        Some(name) => workbook.sheet_names().iter().position(|other| other.eq_ignore_ascii_case(name)),
+        None => (!workbook.sheets.is_empty()).then_some(0),
+    };
+    let sheet = sheet.ok_or_else(|| failed(&XlsxError::UnknownSheet(args.sheet.clone().unwrap_or_default())))?;
+    workbook.sheets[sheet].excel.dialect.has_header = args.header;
+    Ok((workbook, sheet))
+}
+
+// just the sheet, for commands that don't work out formulas.
+fn load_sheet(args: &Args) -> Result<Excel, Failure> {
+    let (mut workbook, sheet) = load(args)?;
+    Ok(workbook.sheets.swap_remove(sheet).excel)
+}
+
+fn read_schema(args: &Args) -> Result<Option<Schema>, Failure> {
+    let path = match &args.schema {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let text = read_to_string(path).map_err(|err| Failure::Message(format!("{}: {}", path, err)))?;
+    let schema = text.parse().map_err(|err: SchemaError| Failure::Message(format!("{}: {}", path, err)))?;
+    Ok(Some(schema))
+}
+
+fn convert(args: &Args) -> Result<ExitCode, Failure> {
+    let (mut workbook, sheet) = load(args)?;
+    let excel = &mut workbook.sheets[sheet].excel;
+    let dialect = excel.dialect;
+    if let Some(schema) = read_schema(args)? {
+        schema.apply(excel).map_err(|err| Failure::Message(err.to_string()))?;
+    }
+
+    excel.dialect = Dialect {
+        delimiter: args.delimiter.unwrap_or(dialect.delimiter),
+        quoting: args.quoting.unwrap_or(dialect.quoting),
+        line_terminator: args.line_terminator.unwrap_or(dialect.line_terminator),
+        ..dialect
+    };
+
+    let excel = &workbook.sheets[sheet].excel;
+    let mut evaluator = Evaluator::for_workbook(&workbook);
+    if args.output == "-" {
+        excel.write_evaluated(stdout().lock(), args.mode, &mut evaluator, sheet)?;
+    } else {
+        let file = File::create(&args.output).map_err(|err| Failure::Message(format!("{}: {}", args.output, err)))?;
+        excel.write_evaluated(file, args.mode, &mut evaluator, sheet)?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+// every malformed row and, with a schema, every cell that doesn't fit its column.
+fn validate(args: &Args) -> Result<ExitCode, Failure> {
+    let schema = read_schema(args)?;
+    let (mut excel, warnings) = if is_xlsx(&args.input) {
+        // there are no rows to get wrong in an .xlsx file, only cells that don't fit the schema.
+        (load_sheet(args)?, Vec::new())
+    } else {
+        let source = open(&args.input)?;
+        let dialect = read_dialect(args, &source);
+        Excel::from_bytes_lenient(&source, dialect).map_err(|err| Failure::Message(format!("{}: {}", args.input, err)))?
+    };
+    let mut out = stdout().lock();
+
+    let mut problems = warnings.len();
+    for warning in &warnings {
+        writeln!(out, "{}", warning)?;
+    }
+    if let Some(schema) = schema {
+        match schema.apply(&mut excel) {
+            Ok(()) => {}
+            Err(SchemaError::Conversion(errors)) => {
+                problems += errors.len();
+                for error in errors {
+                    writeln!(out, "{}", error)?;
+                }
+            }
+            Err(err) => return Err(Failure::Message(err.to_string())),
+        }
+    }
+
+    match problems {
+        0 => {
+            writeln!(out, "ok: {} rows", excel.rows.len())?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        1 => writeln!(out, "1 problem")?,
+        problems => writeln!(out, "{} problems", problems)?,
     }
-    Err(&usage)
+    Ok(ExitCode::from(INVALID))
 }
 
+fn stats(args: &Args) -> Result<ExitCode, Failure> {
+    let excel = load_sheet(args)?;
 
-fn main() {
-    let path = parse_arguments();
-    match path {
-        Ok(p) => {
-            println!("running with file path: {}", p);
-            let mut excel = Excel::from_path(&p).expect("File read failed..."); 
-            let _ = excel.to_file("out.csv").unwrap();
-        },
-        Err(message) => println!("{}", message),
+    let fields = excel.rows.iter().flatten();
+    let blanks = fields.clone().filter(|field| matches!(field.val, FieldValue::Empty)).count();
+    let formulas = fields.filter(|field| matches!(field.val, FieldValue::Formula(_))).count();
+    let mut out = stdout().lock();
+    writeln!(out, "rows: {}", excel.row_count())?;
+    writeln!(out, "columns: {}", excel.column_count())?;
+    writeln!(out, "blank cells: {}", blanks)?;
+    writeln!(out, "formulas: {}", formulas)?;
+    write!(out, "\n{}", Schema::infer(&excel, INFER_ROWS))?;
+    Ok(ExitCode::SUCCESS)
+}
+
+// a range is printed a row at a time. it's cut off at the edge of the grid, the same as in formulas,
+// so A1:XFD1048576 is the whole grid rather than billions of blanks. a cell outside of the grid on its
+// own is blank.
+fn get(args: &Args) -> Result<ExitCode, Failure> {
+    let (workbook, sheet) = load(args)?;
+    let excel = &workbook.sheets[sheet].excel;
+    let (start, end) = args.range.expect("get always has a range");
+    let single = start == end;
+
+    let (first_row, first_col) = (start.row.min(end.row), start.col.min(end.col));
+    let last_row = start.row.max(end.row).min(excel.row_count().saturating_sub(1)).max(first_row);
+    let last_col = start.col.max(end.col).min(excel.column_count().saturating_sub(1)).max(first_col);
+
+    let mut evaluator = Evaluator::for_workbook(&workbook);
+    let rows: Vec<Vec<Computed>> = (first_row..=last_row)
+        .map(|row| {
+            (first_col..=last_col)
+                .map(|col| evaluator.cell(sheet, CellAddress { row, col }))
+                .collect()
+        })
+        .collect();
+
+    let mut out = stdout().lock();
+    match args.format {
+        Format::Text => {
+            for row in &rows {
+                let values: Vec<String> = row.iter().map(|value| value.to_string()).collect();
+                writeln!(out, "{}", values.join("\t"))?;
+            }
+        }
+        Format::Json if single => writeln!(out, "{}", rows[0][0].to_json())?,
+        Format::Json => {
+            let rows: Vec<String> = rows
+                .iter()
+                .map(|row| {
+                    let values: Vec<String> = row.iter().map(Computed::to_json).collect();
+                    format!("[{}]", values.join(","))
+                })
+                .collect();
+            writeln!(out, "[{}]", rows.join(","))?;
+        }
+    }
+
+    if rows.iter().flatten().any(Computed::is_error) {
+        return Ok(ExitCode::from(INVALID));
     }
+    Ok(ExitCode::SUCCESS)
 }
 
-   
+// lines typed into the repl are kept between runs in ~/.excel_takehome_history.
+fn repl(args: &Args) -> Result<ExitCode, Failure> {
+    // stdin is where the lines come from, so no file means starting from nothing.
+    let mut session = if args.input == "-" {
+        Session::new(Excel::new(String::new()))
+    } else {
+        let (workbook, sheet) = load(args)?;
+        Session::with_workbook(workbook, sheet)
+    };
+    println!("{} rows loaded, :help for help", session.excel().rows.len());
 
+    let failed = |err: ReadlineError| Failure::Message(err.to_string());
+    let mut editor = DefaultEditor::new().map_err(failed)?;
+    let history = var_os("HOME").map(|home| PathBuf::from(home).join(".excel_takehome_history"));
+    if let Some(history) = &history {
+        // there's no history the first time around.
+        let _ = editor.load_history(history);
+    }
+
+    loop {
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            // ctrl-c drops the line, the same as in a shell.
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(failed(err)),
+        };
+        if !line.trim().is_empty() {
+            let _ = editor.add_history_entry(line.as_str());
+        }
+        match session.execute(&line) {
+            Ok(Reply::Output(output)) if output.is_empty() => {}
+            Ok(Reply::Output(output)) => println!("{}", output),
+            Ok(Reply::Quit) => break,
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+
+    if let Some(history) = &history {
+        let _ = editor.save_history(history);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+// a number from the environment, like $COLUMNS, which shells don't always pass on.
+fn env_number(name: &str) -> Option<usize> {
+    var_os(name)?.to_str()?.parse().ok()
+}
+
+fn view(args: &Args) -> Result<ExitCode, Failure> {
+    let (workbook, sheet) = load(args)?;
+    let excel = &workbook.sheets[sheet].excel;
+    let mut evaluator = Evaluator::for_workbook(&workbook);
+
+    // paging needs the keyboard, which isn't there when the file came in on stdin.
+    let terminal = stdout().is_terminal();
+    let paging = terminal && stdin().is_terminal() && args.input != "-";
+    let (rows, width) = match paging {
+        // a line is kept back for the header and one for the prompt.
+        true => (env_number("LINES").map_or(40, |lines| lines.saturating_sub(2).max(1)), env_number("COLUMNS").unwrap_or(120)),
+        false => (usize::MAX, usize::MAX),
+    };
+    let mut view = View {
+        row: args.from,
+        rows: args.rows.unwrap_or(rows),
+        width: args.width.unwrap_or(width),
+        cell_width: args.cell_width.unwrap_or(View::default().cell_width),
+        color: args.color.unwrap_or(terminal),
+        sheet,
+        ..View::default()
+    };
+
+    let mut out = stdout().lock();
+    if !paging {
+        write!(out, "{}", view.render(excel, &mut evaluator).text)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let (rows, cols) = (excel.row_count(), excel.column_count());
+    let mut lines = stdin().lock().lines();
+    loop {
+        let page = view.render(excel, &mut evaluator);
+        write!(out, "{}", page.text)?;
+        write!(
+            out,
+            "rows {}-{} of {}, columns {}-{} of {} > ",
+            (view.row + 1).min(rows),
+            (view.row + view.rows).min(rows),
+            rows,
+            CellAddress::column_name(view.col),
+            CellAddress::column_name(view.col + page.columns.max(1) - 1),
+            CellAddress::column_name(cols.max(1) - 1),
+        )?;
+        out.flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        match line.trim() {
+            "" | "n" if view.row + view.rows < rows => view.row += view.rows,
+            "b" | "p" => view.row = view.row.saturating_sub(view.rows),
+            ">" if view.col + page.columns < cols => view.col += page.columns.max(1),
+            // back a page's worth of columns, the page to the left may fit a different number.
+            "<" => view.col = view.col.saturating_sub(page.columns.max(1)),
+            "q" => break,
+            text => {
+                if let Ok(row) = text.parse::<usize>() {
+                    view.row = row.clamp(1, rows.max(1)) - 1;
+                }
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,212 @@
+use std::fmt;
+use std::fs::File;
+use crate::eval::{Computed, Evaluator};
+use crate::excel::{CellAddress, Excel, Field, FieldValue, OutputMode, Value};
+use crate::workbook::Workbook;
+
+pub const HELP: &str = "\
+  =SUM(A1:A10)   work out a formula against the grid
+  B3             show a cell's value
+  B3 = 42        set a cell, =A1*2 sets a formula and \"42\" text
+  :save <file>   write the grid out, formulas and all
+  :help          this
+  :quit          leave, so does ctrl-d";
+
+#[derive(Debug)]
+pub enum ReplError {
+    InvalidReference(String),
+    OutOfRange(CellAddress), // past the end of a sheet, which is as far as a grid can grow.
+    UnknownCommand(String),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for ReplError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplError::InvalidReference(reference) => write!(f, "Invalid cell reference '{}'", reference),
+            ReplError::OutOfRange(address) => {
+                write!(f, "{} is past the end of a sheet, XFD{}", address, CellAddress::MAX_ROWS)
+            }
+            ReplError::UnknownCommand(command) => write!(f, "Unknown command '{}', try :help", command),
+            ReplError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<std::io::Error> for ReplError {
+    fn from(err: std::io::Error) -> Self {
+        ReplError::Io(err)
+    }
+}
+
+pub enum Reply {
+    Output(String),
+    Quit,
+}
+
+// a grid being poked at a line at a time. nothing is kept between lines but the grid itself, so
+// every line is worked out against the latest values. the grid is one sheet of a workbook, and
+// formulas can point at the others.
+pub struct Session {
+    pub workbook: Workbook,
+    pub sheet: usize,
+}
+
+impl Session {
+    // a grid on its own, references to other sheets are #REF!.
+    pub fn new(excel: Excel) -> Self {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("", excel);
+        Self::with_workbook(workbook, 0)
+    }
+
+    pub fn with_workbook(workbook: Workbook, sheet: usize) -> Self {
+        Self { workbook, sheet }
+    }
+
+    pub fn excel(&self) -> &Excel {
+        &self.workbook.sheets[self.sheet].excel
+    }
+
+    pub fn execute(&mut self, line: &str) -> Result<Reply, ReplError> {
+        let line = line.trim();
+        if let Some(command) = line.strip_prefix(':') {
+            return self.command(command);
+        }
+        if line.starts_with('=') {
+            let value = Evaluator::for_workbook(&self.workbook).formula(self.sheet, line);
+            return Ok(Reply::Output(value.to_string()));
+        }
+
+        match line.split_once('=') {
+            Some((reference, text)) => {
+                let address = parse_address(reference.trim())?;
+                Ok(Reply::Output(self.set(address, text.trim())?))
+            }
+            None if line.is_empty() => Ok(Reply::Output(String::new())),
+            None => {
+                let address = parse_address(line)?;
+                Ok(Reply::Output(Evaluator::for_workbook(&self.workbook).cell(self.sheet, address).to_string()))
+            }
+        }
+    }
+
+    fn command(&mut self, command: &str) -> Result<Reply, ReplError> {
+        let (name, argument) = command.split_once(' ').unwrap_or((command, ""));
+        match (name, argument.trim()) {
+            ("q" | "quit" | "exit", _) => Ok(Reply::Quit),
+            ("h" | "help", _) => Ok(Reply::Output(HELP.to_string())),
+            ("save", path) if !path.is_empty() => {
+                self.excel().write_with(File::create(path)?, OutputMode::Formulas)?;
+                Ok(Reply::Output(format!("saved {} rows to {}", self.excel().rows.len(), path)))
+            }
+            _ => Err(ReplError::UnknownCommand(format!(":{}", command))),
+        }
+    }
+
+    // sets the cell, growing the grid if it's past the end, and lists every formula whose value
+    // changed because of it.
+    fn set(&mut self, address: CellAddress, text: &str) -> Result<String, ReplError> {
+        if !address.in_sheet() {
+            return Err(ReplError::OutOfRange(address));
+        }
+        let before = self.formula_values();
+
+        let excel = &mut self.workbook.sheets[self.sheet].excel;
+        let quote = excel.dialect.quote;
+        let value = match text.strip_prefix(quote).and_then(|text| text.strip_suffix(quote)) {
+            Some(text) => FieldValue::String(text.into()),
+            None => Value::parse(text, excel.dialect.decimals).into(),
+        };
+        let rows = &mut excel.rows;
+        if rows.len() <= address.row {
+            rows.resize_with(address.row + 1, Vec::new);
+        }
+        let row = &mut rows[address.row];
+        if row.len() <= address.col {
+            row.resize_with(address.col + 1, || Field::new(FieldValue::Empty));
+        }
+        row[address.col] = Field::new(value);
+
+        let mut evaluator = Evaluator::for_workbook(&self.workbook);
+        let mut lines = vec![format!("{} = {}", address, evaluator.cell(self.sheet, address))];
+        for (other, old) in before {
+            let new = evaluator.cell(self.sheet, other);
+            if other != address && new != old {
+                lines.push(format!("{} = {} (was {})", other, new, old));
+            }
+        }
+        Ok(lines.join("\n"))
+    }
+
+    // every formula cell's current value, in grid order.
+    fn formula_values(&self) -> Vec<(CellAddress, Computed)> {
+        let mut evaluator = Evaluator::for_workbook(&self.workbook);
+        let mut values = Vec::new();
+        for (row, fields) in self.excel().rows.iter().enumerate() {
+            for (col, field) in fields.iter().enumerate() {
+                if matches!(field.val, FieldValue::Formula(_)) {
+                    let address = CellAddress { row, col };
+                    values.push((address, evaluator.cell(self.sheet, address)));
+                }
+            }
+        }
+        values
+    }
+}
+
+fn parse_address(reference: &str) -> Result<CellAddress, ReplError> {
+    CellAddress::parse(reference).ok_or_else(|| ReplError::InvalidReference(reference.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(session: &mut Session, line: &str) -> String {
+        match session.execute(line) {
+            Ok(Reply::Output(output)) => output,
+            Ok(Reply::Quit) => panic!("{:?} quit", line),
+            Err(err) => panic!("{:?} failed: {}", line, err),
+        }
+    }
+
+    #[test]
+    fn test_session() {
+        let mut session = Session::new(Excel::new("1,2,=A1+B1\n3,4,=C1*A2\n".to_string()));
+        assert_eq!(output(&mut session, "=SUM(A1:B2)"), "10");
+        assert_eq!(output(&mut session, "c2"), "9");
+
+        // dependents are worked out again and the ones that changed are listed.
+        assert_eq!(output(&mut session, "A1 = 5"), "A1 = 5\nC1 = 7 (was 3)\nC2 = 21 (was 9)");
+        assert_eq!(output(&mut session, "B2 = =A2*2"), "B2 = 6");
+        assert_eq!(output(&mut session, "B1 = \"2\""), "B1 = 2");
+        assert!(matches!(session.excel().rows[0][1].val, FieldValue::String(_)));
+
+        // past the end of the grid it grows. the text "2" in B1 is skipped by the SUM.
+        assert_eq!(output(&mut session, "D4 = =SUM(A1:B2)"), "D4 = 14");
+        assert_eq!(session.excel().rows.len(), 4);
+        assert_eq!(output(&mut session, "D3"), "");
+
+        assert!(matches!(session.execute("=Other!A1"), Ok(Reply::Output(output)) if output == "#REF!"));
+        assert!(matches!(session.execute("A0"), Err(ReplError::InvalidReference(_))));
+        // the grid grows as far as a sheet goes and no further.
+        assert!(matches!(session.execute("A1000000000 = 1"), Err(ReplError::OutOfRange(_))));
+        assert!(matches!(session.execute("XFE1 = 1"), Err(ReplError::OutOfRange(_))));
+        assert_eq!(session.excel().rows.len(), 4);
+        assert!(matches!(session.execute(":nope"), Err(ReplError::UnknownCommand(_))));
+        assert!(matches!(session.execute(":quit"), Ok(Reply::Quit)));
+    }
+
+    #[test]
+    fn test_other_sheets() {
+        let workbook = Workbook::from_xlsx("test_data/workbook.xlsx").unwrap();
+        let sheet = workbook.sheet_names().iter().position(|name| *name == "Summary").unwrap();
+        let mut session = Session::with_workbook(workbook, sheet);
+        assert_eq!(output(&mut session, "B2"), "apple");
+        assert_eq!(output(&mut session, "=Data!B2*2"), "6");
+        // a change to this sheet shows up in the formulas that point at it from the others.
+        assert_eq!(output(&mut session, "B1 = 20"), "B1 = 20");
+        assert_eq!(output(&mut session, "=Data!F6"), "60");
+    }
+}
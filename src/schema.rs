@@ -0,0 +1,475 @@
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use crate::date::Date;
+use crate::decimal::Decimal;
+use crate::excel::{CellAddress, Excel, FieldValue};
+
+// date formats tried when inferring a schema, in order.
+const DATE_FORMATS: [&str; 5] = ["%Y-%m-%d", "%Y/%m/%d", "%d/%m/%Y", "%m/%d/%Y", "%d.%m.%Y"];
+
+// a column with this many different values or fewer, each showing up a few times, is inferred as an enum.
+const MAX_ENUM_VALUES: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColumnType {
+    Integer,
+    Float,
+    Decimal,
+    Bool,
+    Date(String), // the format the dates are written in, like "%d/%m/%Y".
+    String,
+    Enum(Vec<String>), // text that has to be one of these values.
+}
+
+impl ColumnType {
+    // the value of a cell in a column of this type, None when the text doesn't fit.
+    pub fn convert(&self, text: &str) -> Option<FieldValue> {
+        match self {
+            ColumnType::Integer => text.parse().ok().map(FieldValue::Integer),
+            ColumnType::Float => text.parse().ok().map(FieldValue::Float),
+            ColumnType::Decimal => text.parse().ok().map(FieldValue::Decimal),
+            ColumnType::Bool => parse_bool(text).map(FieldValue::Bool),
+            ColumnType::Date(format) => Date::parse(text, format).map(FieldValue::Date),
+            ColumnType::String => Some(FieldValue::String(text.into())),
+            ColumnType::Enum(values) => values
+                .iter()
+                .any(|value| value == text)
+                .then(|| FieldValue::String(text.into())),
+        }
+    }
+}
+
+fn parse_bool(text: &str) -> Option<bool> {
+    match text.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "y" | "t" | "1" => Some(true),
+        "false" | "no" | "n" | "f" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+// written the same way a schema is read back in, "date(%d/%m/%Y)" or "enum(red|green|blue)".
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColumnType::Integer => write!(f, "integer"),
+            ColumnType::Float => write!(f, "float"),
+            ColumnType::Decimal => write!(f, "decimal"),
+            ColumnType::Bool => write!(f, "bool"),
+            ColumnType::Date(format) => write!(f, "date({})", format),
+            ColumnType::String => write!(f, "string"),
+            ColumnType::Enum(values) => write!(f, "enum({})", values.join("|")),
+        }
+    }
+}
+
+impl FromStr for ColumnType {
+    type Err = SchemaError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        let (name, argument) = match text.split_once('(') {
+            Some((name, rest)) => {
+                let argument = rest
+                    .strip_suffix(')')
+                    .ok_or_else(|| SchemaError::InvalidType(text.to_string()))?;
+                (name.trim(), Some(argument))
+            }
+            None => (text, None),
+        };
+
+        match (name.to_ascii_lowercase().as_str(), argument) {
+            ("integer", None) => Ok(ColumnType::Integer),
+            ("float", None) => Ok(ColumnType::Float),
+            ("decimal", None) => Ok(ColumnType::Decimal),
+            ("bool", None) => Ok(ColumnType::Bool),
+            ("date", None) => Ok(ColumnType::Date(DATE_FORMATS[0].to_string())),
+            ("date", Some(format)) => Ok(ColumnType::Date(format.to_string())),
+            ("string", None) => Ok(ColumnType::String),
+            ("enum", Some(values)) => Ok(ColumnType::Enum(values.split('|').map(str::to_string).collect())),
+            _ => Err(SchemaError::InvalidType(text.to_string())),
+        }
+    }
+}
+
+// which column a type is for, by its header or by position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColumnRef {
+    Index(usize),
+    Name(String), // a header, or upper case column letters like "C" when no header has that name.
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnSchema {
+    pub column: ColumnRef,
+    pub kind: ColumnType,
+}
+
+// the type of each column, so cells are converted to what they're declared as instead of being
+// guessed one by one. columns that aren't in the schema are read as usual.
+//
+// a schema is written one column to a line, "name: type", and the header row is left as text:
+//
+//     id: integer
+//     price: decimal
+//     shipped: date(%d/%m/%Y)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Schema {
+    pub columns: Vec<ColumnSchema>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn column(mut self, column: ColumnRef, kind: ColumnType) -> Self {
+        self.columns.push(ColumnSchema { column, kind });
+        self
+    }
+
+    // guesses each column's type from the first `rows` rows under the header, meant to be printed
+    // and checked over before it's used. a type is only picked when every non empty cell fits it.
+    pub fn infer(excel: &Excel, rows: usize) -> Self {
+        let header = first_row(excel).filter(|_| excel.dialect.has_header);
+        let first = header.is_some() as usize;
+        let sample = excel.rows.iter().skip(first).take(rows);
+
+        let mut columns: Vec<Vec<String>> = Vec::new();
+        for row in sample {
+            for (col, field) in row.iter().enumerate() {
+                if columns.len() <= col {
+                    columns.resize(col + 1, Vec::new());
+                }
+                let text = field.text(&excel.dialect);
+                if !text.is_empty() {
+                    columns[col].push(text.into_owned());
+                }
+            }
+        }
+
+        let mut schema = Schema::new();
+        for (col, texts) in columns.iter().enumerate() {
+            let column = match header.as_ref().and_then(|header| header.get(col)) {
+                Some(name) if !name.is_empty() => ColumnRef::Name(name.clone()),
+                _ => ColumnRef::Name(CellAddress::column_name(col)),
+            };
+            schema = schema.column(column, infer_type(texts, excel.dialect.decimals));
+        }
+        schema
+    }
+
+    // converts every cell in a declared column. all the cells that don't fit are reported, with
+    // their address, rather than stopping at the first one, and then nothing is converted at all.
+    // the first row is taken as the header when the dialect says so, or when a column is named
+    // after something in it.
+    pub fn apply(&self, excel: &mut Excel) -> Result<(), SchemaError> {
+        let header = first_row(excel).filter(|first_row| {
+            excel.dialect.has_header
+                || self.columns.iter().any(|column| match &column.column {
+                    ColumnRef::Name(name) => first_row.contains(name),
+                    ColumnRef::Index(_) => false,
+                })
+        });
+        let mut columns = Vec::new();
+        for column in &self.columns {
+            columns.push((resolve(&column.column, header.as_deref())?, &column.kind));
+        }
+
+        let dialect = excel.dialect;
+        let first = header.is_some() as usize;
+        let mut errors = Vec::new();
+        let mut converted = Vec::new();
+        for (row, fields) in excel.rows.iter().enumerate().skip(first) {
+            for &(col, kind) in &columns {
+                let field = match fields.get(col) {
+                    Some(field) => field,
+                    None => continue,
+                };
+                // formulas are worked out later, and an empty cell fits any type.
+                let text = field.text(&dialect);
+                if text.is_empty() || matches!(field.val, FieldValue::Formula(_)) {
+                    continue;
+                }
+                // strings that fit don't need converting, that would lose where their text is kept.
+                if matches!(kind, ColumnType::String | ColumnType::Enum(_)) && matches!(field.val, FieldValue::String(_)) {
+                    if kind.convert(&text).is_none() {
+                        errors.push(ConversionError::new(row, col, kind, &text));
+                    }
+                    continue;
+                }
+
+                match kind.convert(&text) {
+                    Some(val) => converted.push((row, col, val)),
+                    None => errors.push(ConversionError::new(row, col, kind, &text)),
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(SchemaError::Conversion(errors));
+        }
+        for (row, col, val) in converted {
+            excel.rows[row][col].retype(val, &dialect);
+        }
+        Ok(())
+    }
+}
+
+fn first_row(excel: &Excel) -> Option<Vec<String>> {
+    let row = excel.rows.first()?;
+    Some(row.iter().map(|field| field.text(&excel.dialect).into_owned()).collect())
+}
+
+fn resolve(column: &ColumnRef, header: Option<&[String]>) -> Result<usize, SchemaError> {
+    let name = match column {
+        ColumnRef::Index(col) => return Ok(*col),
+        ColumnRef::Name(name) => name,
+    };
+    header
+        .and_then(|header| header.iter().position(|title| title == name))
+        .or_else(|| match name.chars().all(|c| c.is_ascii_uppercase()) {
+            true => CellAddress::parse_column(name),
+            false => None,
+        })
+        .ok_or_else(|| SchemaError::UnknownColumn(name.clone()))
+}
+
+fn infer_type(texts: &[String], decimals: bool) -> ColumnType {
+    let all = |fits: &dyn Fn(&str) -> bool| texts.iter().all(|text| fits(text));
+    if texts.is_empty() {
+        return ColumnType::String;
+    }
+
+    // a leading zero is a code, like a zip code, not a number.
+    let leading_zero = |text: &str| {
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.")
+    };
+    if !texts.iter().any(|text| leading_zero(text)) {
+        if all(&|text| text.parse::<i64>().is_ok()) {
+            return ColumnType::Integer;
+        }
+        if decimals && all(&|text| text.parse::<Decimal>().is_ok()) {
+            return ColumnType::Decimal;
+        }
+        if all(&|text| text.parse::<f64>().is_ok()) {
+            return ColumnType::Float;
+        }
+    }
+    if all(&|text| parse_bool(text).is_some()) {
+        return ColumnType::Bool;
+    }
+    for format in DATE_FORMATS {
+        if all(&|text| Date::parse(text, format).is_some()) {
+            return ColumnType::Date(format.to_string());
+        }
+    }
+
+    let mut values: Vec<String> = Vec::new();
+    for text in texts {
+        if !values.contains(text) {
+            values.push(text.clone());
+            if values.len() > MAX_ENUM_VALUES {
+                return ColumnType::String;
+            }
+        }
+    }
+    if values.len() * 4 <= texts.len() {
+        ColumnType::Enum(values)
+    } else {
+        ColumnType::String
+    }
+}
+
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for column in &self.columns {
+            match &column.column {
+                ColumnRef::Index(col) => write!(f, "{}", CellAddress::column_name(*col))?,
+                ColumnRef::Name(name) => write!(f, "{}", name)?,
+            }
+            writeln!(f, ": {}", column.kind)?;
+        }
+        Ok(())
+    }
+}
+
+// reads the same format Display writes, blank lines and lines starting with # are skipped.
+impl FromStr for Schema {
+    type Err = SchemaError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut schema = Schema::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, kind) = line
+                .rsplit_once(':')
+                .ok_or_else(|| SchemaError::InvalidType(line.to_string()))?;
+            schema = schema.column(ColumnRef::Name(name.trim().to_string()), kind.parse()?);
+        }
+        Ok(schema)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversionError {
+    pub address: CellAddress,
+    pub expected: ColumnType,
+    pub text: String,
+}
+
+impl ConversionError {
+    fn new(row: usize, col: usize, expected: &ColumnType, text: &str) -> Self {
+        Self {
+            address: CellAddress { row, col },
+            expected: expected.clone(),
+            text: text.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: expected {}, found {:?}", self.address, self.expected, self.text)
+    }
+}
+
+#[derive(Debug)]
+pub enum SchemaError {
+    InvalidType(String),
+    UnknownColumn(String),
+    Conversion(Vec<ConversionError>), // every cell that didn't fit its column's type.
+    Io(io::Error),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaError::InvalidType(text) => write!(f, "Invalid column type '{}'", text),
+            SchemaError::UnknownColumn(name) => write!(f, "No column named '{}'", name),
+            SchemaError::Conversion(errors) => {
+                match errors.len() {
+                    1 => write!(f, "1 cell doesn't match the schema")?,
+                    count => write!(f, "{} cells don't match the schema", count)?,
+                }
+                for error in errors {
+                    write!(f, "\n{}", error)?;
+                }
+                Ok(())
+            }
+            SchemaError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl From<io::Error> for SchemaError {
+    fn from(err: io::Error) -> Self {
+        SchemaError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv::Dialect;
+    use crate::excel::OutputMode;
+
+    fn load(src: &str) -> Excel {
+        let dialect = Dialect { has_header: true, ..Dialect::default() };
+        Excel::with_dialect(src.to_string(), dialect).unwrap()
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut excel = load("id,zip,price,paid,shipped,size\n1,\"00123\",-35.70,yes,19/10/2026,S\n2,4501,3,no,,M\n");
+        let schema: Schema = "id: integer\nzip: string\nprice: decimal\npaid: bool\nshipped: date(%d/%m/%Y)\nF: enum(S|M|L)"
+            .parse()
+            .unwrap();
+        schema.apply(&mut excel).unwrap();
+
+        let row = &excel.rows[1];
+        assert!(matches!(row[0].val, FieldValue::Integer(1)));
+        assert!(matches!(&row[1].val, FieldValue::String(text) if text == "00123"));
+        assert!(matches!(&row[2].val, FieldValue::Decimal(d) if d.to_string() == "-35.70"));
+        assert!(matches!(row[3].val, FieldValue::Bool(true)));
+        assert!(matches!(row[4].val, FieldValue::Date(d) if d == Date::new(2026, 10, 19).unwrap()));
+        // declared as text, so the number isn't a number any more.
+        assert!(matches!(&excel.rows[2][1].val, FieldValue::String(text) if text == "4501"));
+        assert!(matches!(&excel.rows[2][2].val, FieldValue::Decimal(d) if d.to_string() == "3"));
+        // the header is left alone.
+        assert!(matches!(&excel.rows[0][0].val, FieldValue::String(text) if text == "id"));
+    }
+
+    #[test]
+    fn test_written_back() {
+        let src = "id,paid,shipped,price\n1,yes,19/10/2026,-35.70\n2,n,1/2/2026,3\n";
+        let schema: Schema = "id: integer\npaid: bool\nshipped: date(%d/%m/%Y)\nprice: float".parse().unwrap();
+        let mut excel = load(src);
+        schema.apply(&mut excel).unwrap();
+
+        // the cells are written the way they were read, so the same schema reads them back in.
+        let mut written = Vec::new();
+        excel.write_with(&mut written, OutputMode::Values).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        assert_eq!(written, src);
+        let mut excel = load(&written);
+        schema.apply(&mut excel).unwrap();
+        assert!(matches!(excel.rows[2][2].val, FieldValue::Date(d) if d == Date::new(2026, 2, 1).unwrap()));
+        assert_eq!(excel.rows[1][1].text(&excel.dialect), "yes");
+
+        // a cell changed since is written from its new value.
+        excel.rows[1][2].val = FieldValue::Date(Date::new(2026, 10, 20).unwrap());
+        assert_eq!(excel.rows[1][2].to_csv(&excel.dialect), "2026-10-20");
+    }
+
+    #[test]
+    fn test_conversion_errors() {
+        let mut excel = load("id,size\n1,S\nx,XL\n3.5,M\n");
+        let schema = Schema::new()
+            .column(ColumnRef::Name("id".to_string()), ColumnType::Integer)
+            .column(ColumnRef::Index(1), ColumnType::Enum(vec!["S".to_string(), "M".to_string()]));
+        let errors = match schema.apply(&mut excel) {
+            Err(SchemaError::Conversion(errors)) => errors,
+            other => panic!("{:?}", other),
+        };
+        let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec!["A3: expected integer, found \"x\"", "B3: expected enum(S|M), found \"XL\"", "A4: expected integer, found \"3.5\""]
+        );
+
+        // the cells that did fit are left as they were too.
+        let schema = Schema::new().column(ColumnRef::Name("id".to_string()), ColumnType::Float);
+        assert!(schema.apply(&mut excel).is_err());
+        assert!(matches!(excel.rows[1][0].val, FieldValue::Integer(1)));
+
+        let schema = Schema::new().column(ColumnRef::Name("missing".to_string()), ColumnType::Integer);
+        assert!(matches!(schema.apply(&mut excel), Err(SchemaError::UnknownColumn(_))));
+        assert!(matches!("id: number".parse::<Schema>(), Err(SchemaError::InvalidType(_))));
+    }
+
+    #[test]
+    fn test_infer() {
+        let mut src = "id,zip,price,when,flag,kind,name\n".to_string();
+        for i in 0..8 {
+            src += &format!("{},\"0{}\",{}.5,2026-10-{:02},{},{},name {}\n", i, i, i, i + 1, i % 2 == 0, ["a", "b"][i % 2], i);
+        }
+        let excel = load(&src);
+        let schema = Schema::infer(&excel, 100);
+        assert_eq!(
+            schema.to_string(),
+            "id: integer\nzip: string\nprice: float\nwhen: date(%Y-%m-%d)\nflag: bool\nkind: enum(a|b)\nname: string\n"
+        );
+
+        // what's printed can be read back in and used.
+        let mut excel = load(&src);
+        schema.to_string().parse::<Schema>().unwrap().apply(&mut excel).unwrap();
+        assert!(matches!(excel.rows[1][3].val, FieldValue::Date(_)));
+        assert_eq!(Schema::infer(&excel, 100), schema);
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Deref;
+use memmap2::Mmap;
+
+// the raw bytes of an input. regular files are memory mapped so parsing can borrow straight out of
+// the page cache instead of copying the whole file onto the heap first. anything that can't be
+// mapped, like a pipe or stdin, is read into memory instead.
+pub enum Source {
+    Mapped(Mmap),
+    Buffered(Vec<u8>),
+}
+
+impl Source {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let metadata = file.metadata()?;
+        // mapping an empty file is an error on some platforms, and there's nothing to save anyway.
+        if !metadata.is_file() || metadata.len() == 0 {
+            return Self::from_reader(file);
+        }
+
+        // SAFETY: the map hands out plain byte slices, so this is only sound as long as nothing else
+        // writes to or truncates the file while it's open. if another process does, reads can come
+        // back changed or fail with SIGBUS, and there's no way to guard against that from in here, it's
+        // the same trade every tool that maps its input makes. files that might change underneath us
+        // should be piped in on stdin instead, which gets read into memory.
+        match unsafe { Mmap::map(&file) } {
+            Ok(map) => Ok(Source::Mapped(map)),
+            Err(_) => Self::from_reader(&mut file),
+        }
+    }
+
+    pub fn from_reader<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        Ok(Source::Buffered(buffer))
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        matches!(self, Source::Mapped(_))
+    }
+}
+
+impl Deref for Source {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Source::Mapped(map) => map,
+            Source::Buffered(buffer) => buffer,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open() {
+        let source = Source::open("test_data/primitives.csv").unwrap();
+        assert!(source.is_mapped());
+        assert_eq!(&source[..], &std::fs::read("test_data/primitives.csv").unwrap()[..]);
+
+        // a character device can't be mapped, it's read like a pipe would be.
+        #[cfg(unix)]
+        {
+            let source = Source::open("/dev/null").unwrap();
+            assert!(!source.is_mapped());
+            assert!(source.is_empty());
+        }
+
+        let source = Source::from_reader(&b"a,b\n"[..]).unwrap();
+        assert_eq!(&source[..], b"a,b\n");
+    }
+}
@@ -0,0 +1,8 @@
+// helpers shared by the tests in more than one module.
+use std::path::PathBuf;
+
+// a file in the temp dir only this test run uses, so runs going at the same time don't write over
+// each other's files.
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("excel_takehome_{}_{}", std::process::id(), name))
+}
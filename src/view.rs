@@ -0,0 +1,184 @@
+use crate::eval::{Computed, Evaluator};
+use crate::excel::{CellAddress, Excel, FieldValue, Grid};
+
+const BOLD: &str = "\x1b[1m";
+const FORMULA: &str = "\x1b[36m"; // cyan
+const ERROR: &str = "\x1b[31m"; // red
+const RESET: &str = "\x1b[0m";
+
+// which part of the grid to draw and how. rows and columns are zero based, `width` is the most
+// characters a line can take, so only as many columns as fit are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct View {
+    pub row: usize,
+    pub col: usize,
+    pub rows: usize,
+    pub width: usize,
+    pub cell_width: usize, // longer cells are cut short with a "~".
+    pub color: bool, // formulas and errors stand out, for terminals.
+    pub sheet: usize, // which of the evaluator's sheets the grid is.
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self {
+            row: 0,
+            col: 0,
+            rows: 40,
+            width: 120,
+            cell_width: 20,
+            color: false,
+            sheet: 0,
+        }
+    }
+}
+
+// a drawn page, with how many columns made it in so the next page across can start after them.
+pub struct Page {
+    pub text: String,
+    pub columns: usize,
+}
+
+// how a cell is drawn, numbers line up on the right.
+struct Cell {
+    text: String,
+    right: bool,
+    color: Option<&'static str>,
+}
+
+impl View {
+    // formulas are shown as what they work out to.
+    pub fn render(&self, excel: &Excel, evaluator: &mut Evaluator) -> Page {
+        let last_row = self.row.saturating_add(self.rows).min(excel.row_count());
+        let rows = self.row.min(last_row)..last_row;
+        let label_width = last_row.max(1).to_string().len();
+
+        let mut columns = Vec::new();
+        let mut line_width = label_width;
+        for col in self.col..excel.column_count() {
+            let cells: Vec<Cell> = rows.clone().map(|row| self.cell(excel, evaluator, CellAddress { row, col })).collect();
+            let name = CellAddress::column_name(col);
+            let width = cells
+                .iter()
+                .map(|cell| cell.text.chars().count())
+                .chain([name.len()])
+                .max()
+                .unwrap_or(0);
+            // the first column is always drawn, however narrow the screen.
+            if !columns.is_empty() && line_width + 2 + width > self.width {
+                break;
+            }
+            line_width += 2 + width;
+            columns.push((name, width, cells));
+        }
+
+        let mut text = String::new();
+        let header = Cell { text: String::new(), right: true, color: Some(BOLD) };
+        self.push(&mut text, &header, label_width);
+        for (name, width, _) in &columns {
+            text.push_str("  ");
+            self.push(&mut text, &Cell { text: name.clone(), right: false, color: Some(BOLD) }, *width);
+        }
+        trim_line(&mut text);
+
+        for (idx, row) in rows.enumerate() {
+            let label = Cell { text: (row + 1).to_string(), right: true, color: Some(BOLD) };
+            self.push(&mut text, &label, label_width);
+            for (_, width, cells) in &columns {
+                text.push_str("  ");
+                self.push(&mut text, &cells[idx], *width);
+            }
+            trim_line(&mut text);
+        }
+
+        Page {
+            text,
+            columns: columns.len(),
+        }
+    }
+
+    fn cell(&self, excel: &Excel, evaluator: &mut Evaluator, address: CellAddress) -> Cell {
+        let formula = matches!(excel.get(address).map(|field| &field.val), Some(FieldValue::Formula(_)));
+        let value = evaluator.cell(self.sheet, address);
+        let color = match value {
+            Computed::Error(_) => Some(ERROR),
+            _ if formula => Some(FORMULA),
+            _ => None,
+        };
+        Cell {
+            text: truncate(value.to_string(), self.cell_width),
+            right: matches!(value, Computed::Number(_)),
+            color,
+        }
+    }
+
+    // pads the cell out to `width`. the colour codes only go around the text, so trailing padding
+    // can still be trimmed off the end of the line.
+    fn push(&self, text: &mut String, cell: &Cell, width: usize) {
+        let padding = " ".repeat(width.saturating_sub(cell.text.chars().count()));
+        if cell.right {
+            text.push_str(&padding);
+        }
+        match cell.color {
+            Some(color) if self.color && !cell.text.is_empty() => {
+                text.push_str(color);
+                text.push_str(&cell.text);
+                text.push_str(RESET);
+            }
+            _ => text.push_str(&cell.text),
+        }
+        if !cell.right {
+            text.push_str(&padding);
+        }
+    }
+}
+
+// line breaks and tabs would break up the table, so they're drawn as spaces.
+fn truncate(text: String, width: usize) -> String {
+    let text = text.replace(['\n', '\r', '\t'], " ");
+    if text.chars().count() <= width {
+        return text;
+    }
+    let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+    cut.push('~');
+    cut
+}
+
+fn trim_line(text: &mut String) {
+    let trimmed = text.trim_end_matches(' ').len();
+    text.truncate(trimmed);
+    text.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(src: &str, view: View) -> Page {
+        let excel = Excel::new(src.to_string());
+        let mut evaluator = Evaluator::new(&excel);
+        view.render(&excel, &mut evaluator)
+    }
+
+    #[test]
+    fn test_render() {
+        let src = "id,name,total\n1,a very long name indeed,=A2*10\n2,b,=1/0\n";
+        let view = View { cell_width: 10, ..View::default() };
+        let page = render(src, view);
+        assert_eq!(page.columns, 3);
+        assert_eq!(
+            page.text,
+            "   A   B           C\n1  id  name        total\n2   1  a very lo~       10\n3   2  b           #DIV/0!\n"
+        );
+
+        // a page further down and across, only as many columns as fit the width.
+        let page = render(src, View { row: 1, col: 1, rows: 1, width: 15, ..view });
+        assert_eq!(page.columns, 1);
+        assert_eq!(page.text, "   B\n2  a very lo~\n");
+
+        // formulas and errors are coloured, the padding stays outside the colour codes.
+        let page = render("=1+1,=1/0\n", View { color: true, ..View::default() });
+        let lines: Vec<&str> = page.text.lines().collect();
+        assert_eq!(lines[1], "\x1b[1m1\x1b[0m  \x1b[36m2\x1b[0m  \x1b[31m#DIV/0!\x1b[0m");
+    }
+}
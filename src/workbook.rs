@@ -0,0 +1,104 @@
+use std::fs::{read_dir, File};
+use std::path::Path;
+use crate::excel::Excel;
+use crate::xlsx::{read_sheets, XlsxError};
+
+pub struct Sheet {
+    pub name: String,
+    pub excel: Excel,
+}
+
+// a collection of named sheets, formulas in one sheet can point at another with Sheet2!A1.
+#[derive(Default)]
+pub struct Workbook {
+    pub sheets: Vec<Sheet>,
+}
+
+impl Workbook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // loads every csv file in the directory as a sheet named after the file, e.g. "Sheet2.csv" -> "Sheet2".
+    pub fn from_dir(path: &str) -> std::io::Result<Self> {
+        let mut paths = Vec::new();
+        for entry in read_dir(path)? {
+            let entry_path = entry?.path();
+            let is_csv = entry_path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+            if entry_path.is_file() && is_csv {
+                paths.push(entry_path);
+            }
+        }
+
+        // read_dir has no guaranteed order, keep the sheets stable between runs.
+        paths.sort();
+
+        let mut workbook = Self::new();
+        for sheet_path in paths {
+            let name = sheet_name(&sheet_path);
+            let excel = Excel::from_path(&sheet_path.to_string_lossy())?;
+            workbook.add_sheet(&name, excel);
+        }
+        Ok(workbook)
+    }
+
+    // every sheet of an .xlsx file, keeping the names and order they have in excel.
+    pub fn from_xlsx(path: &str) -> Result<Self, XlsxError> {
+        let mut workbook = Self::new();
+        for (name, excel) in read_sheets(File::open(path)?)? {
+            workbook.add_sheet(&name, excel);
+        }
+        Ok(workbook)
+    }
+
+    pub fn add_sheet(&mut self, name: &str, excel: Excel) {
+        self.sheets.push(Sheet {
+            name: name.to_string(),
+            excel,
+        });
+    }
+
+    // sheet names are case insensitive, same as in excel.
+    pub fn sheet(&self, name: &str) -> Option<&Excel> {
+        self.sheets
+            .iter()
+            .find(|sheet| sheet.name.eq_ignore_ascii_case(name))
+            .map(|sheet| &sheet.excel)
+    }
+
+    pub fn sheet_names(&self) -> Vec<&str> {
+        self.sheets.iter().map(|sheet| sheet.name.as_str()).collect()
+    }
+}
+
+fn sheet_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{Computed, Evaluator};
+
+    #[test]
+    fn test_from_dir() {
+        let workbook = Workbook::from_dir("test_data/workbook").unwrap();
+        assert_eq!(workbook.sheet_names(), vec!["My Sheet", "Sheet1", "Sheet2"]);
+        assert!(workbook.sheet("sheet2").is_some());
+        assert!(workbook.sheet("Sheet3").is_none());
+    }
+
+    #[test]
+    fn test_cross_sheet_references() {
+        let workbook = Workbook::from_dir("test_data/workbook").unwrap();
+        let sheet = workbook.sheet_names().iter().position(|name| *name == "Sheet1").unwrap();
+        let mut evaluator = Evaluator::for_workbook(&workbook);
+        assert_eq!(evaluator.formula(sheet, "=Sheet2!A1"), Computed::Number(10.0));
+        assert_eq!(evaluator.formula(sheet, "=COUNTA('My Sheet'!A1:B2)"), Computed::Number(4.0));
+        assert_eq!(evaluator.formula(sheet, "=C1"), Computed::Number(3.0));
+    }
+}
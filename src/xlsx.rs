@@ -0,0 +1,756 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Seek};
+use std::sync::Arc;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use zip::result::ZipError;
+use zip::ZipArchive;
+use crate::csv::Dialect;
+use crate::date::Date;
+use crate::excel::{CellAddress, Excel, Field, FieldValue, Text, Value};
+use crate::interner::Interner;
+
+#[derive(Debug)]
+pub enum XlsxError {
+    Io(io::Error),
+    Zip(ZipError), // not a zip file, or a broken one.
+    Xml { part: String, message: String }, // a part of the file that isn't the xml it should be.
+    MissingPart(String),
+    TooLarge(String), // a part that unzips to more than MAX_PART_SIZE.
+    OutOfRange { part: String, reference: String }, // a row or cell past the end of an excel sheet.
+    UnknownSheet(String),
+}
+
+impl fmt::Display for XlsxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            XlsxError::Io(err) => write!(f, "{}", err),
+            XlsxError::Zip(err) => write!(f, "Not a readable xlsx file: {}", err),
+            XlsxError::Xml { part, message } => write!(f, "Invalid xml in {}: {}", part, message),
+            XlsxError::MissingPart(part) => write!(f, "The file has no {}", part),
+            XlsxError::TooLarge(part) => write!(f, "{} unzips to more than {} MB", part, MAX_PART_SIZE >> 20),
+            XlsxError::OutOfRange { part, reference } => {
+                write!(f, "{} in {} is past the end of a sheet, XFD{}", reference, part, CellAddress::MAX_ROWS)
+            }
+            XlsxError::UnknownSheet(name) => write!(f, "No sheet named '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for XlsxError {}
+
+impl From<io::Error> for XlsxError {
+    fn from(err: io::Error) -> Self {
+        XlsxError::Io(err)
+    }
+}
+
+impl From<ZipError> for XlsxError {
+    fn from(err: ZipError) -> Self {
+        XlsxError::Zip(err)
+    }
+}
+
+// reads every sheet of an .xlsx workbook, in the order excel shows them. an xlsx file is a zip of
+// xml parts: the workbook lists the sheets, each sheet's cells point into one shared list of
+// strings and styles say which numbers are dates.
+//
+// formulas are kept as formulas to be worked out again, the values excel saved alongside them
+// are dropped. error cells like #N/A are read as text.
+pub fn read_sheets<R: Read + Seek>(reader: R) -> Result<Vec<(String, Excel)>, XlsxError> {
+    let mut archive = ZipArchive::new(reader)?;
+
+    // the workbook is nearly always xl/workbook.xml, the package relationships say for sure.
+    let workbook_path = match read_part(&mut archive, "_rels/.rels")? {
+        Some(rels) => relationships(&rels, "_rels/.rels", "")?
+            .into_iter()
+            .find(|rel| rel.kind.ends_with("/officeDocument"))
+            .map(|rel| rel.target),
+        None => None,
+    };
+    let workbook_path = workbook_path.unwrap_or_else(|| "xl/workbook.xml".to_string());
+    let base = match workbook_path.rfind('/') {
+        Some(idx) => &workbook_path[..=idx],
+        None => "",
+    };
+
+    let workbook = read_part(&mut archive, &workbook_path)?.ok_or_else(|| XlsxError::MissingPart(workbook_path.clone()))?;
+    let rels_path = format!("{}_rels/{}.rels", base, &workbook_path[base.len()..]);
+    let rels = match read_part(&mut archive, &rels_path)? {
+        Some(rels) => relationships(&rels, &rels_path, base)?,
+        None => Vec::new(),
+    };
+    let part_of_kind = |kind: &str, default: &str| {
+        rels.iter()
+            .find(|rel| rel.kind.ends_with(kind))
+            .map_or_else(|| format!("{}{}", base, default), |rel| rel.target.clone())
+    };
+
+    let mut pool = Interner::new();
+    let shared_strings_path = part_of_kind("/sharedStrings", "sharedStrings.xml");
+    let shared_strings = match read_part(&mut archive, &shared_strings_path)? {
+        Some(xml) => shared_strings(&xml, &shared_strings_path, &mut pool)?,
+        None => Vec::new(),
+    };
+    let styles_path = part_of_kind("/styles", "styles.xml");
+    let date_styles = match read_part(&mut archive, &styles_path)? {
+        Some(xml) => date_styles(&xml, &styles_path)?,
+        None => Vec::new(),
+    };
+
+    let mut sheets = Vec::new();
+    for (name, id) in sheet_list(&workbook, &workbook_path)? {
+        let path = rels
+            .iter()
+            .find(|rel| rel.id == id)
+            .map(|rel| rel.target.clone())
+            .ok_or_else(|| XlsxError::MissingPart(format!("sheet '{}'", name)))?;
+        let xml = read_part(&mut archive, &path)?.ok_or_else(|| XlsxError::MissingPart(path.clone()))?;
+        let mut sheet = SheetReader {
+            shared_strings: &shared_strings,
+            date_styles: &date_styles,
+            pool: &mut pool,
+            masters: HashMap::new(),
+        };
+        let rows = sheet.read(&xml, &path)?;
+        sheets.push((name, Excel { rows, dialect: Dialect::default() }));
+    }
+    Ok(sheets)
+}
+
+// the most a part is unzipped to, so a small file that unzips to something enormous is turned away
+// instead of filling up memory. a sheet this big would be millions of cells more than any real one.
+const MAX_PART_SIZE: u64 = 1 << 30;
+
+// None when the zip has no such part, most of them are optional.
+fn read_part<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Result<Option<String>, XlsxError> {
+    let file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    if file.size() > MAX_PART_SIZE {
+        return Err(XlsxError::TooLarge(name.to_string()));
+    }
+    read_limited(file, name, MAX_PART_SIZE).map(Some)
+}
+
+// the size in the zip's header is only what it claims, so the limit is kept to while reading too.
+fn read_limited(file: impl Read, name: &str, limit: u64) -> Result<String, XlsxError> {
+    let mut text = String::new();
+    file.take(limit + 1).read_to_string(&mut text)?;
+    if text.len() as u64 > limit {
+        return Err(XlsxError::TooLarge(name.to_string()));
+    }
+    Ok(text)
+}
+
+fn out_of_range(part: &str, reference: String) -> XlsxError {
+    XlsxError::OutOfRange {
+        part: part.to_string(),
+        reference,
+    }
+}
+
+fn xml_error(part: &str, err: impl fmt::Display) -> XlsxError {
+    XlsxError::Xml {
+        part: part.to_string(),
+        message: err.to_string(),
+    }
+}
+
+// calls `handle` with every event up to the end of the document.
+fn each_event<'a>(
+    xml: &'a str,
+    part: &str,
+    mut handle: impl FnMut(Event<'a>) -> Result<(), XlsxError>,
+) -> Result<(), XlsxError> {
+    let mut reader = Reader::from_str(xml);
+    loop {
+        match reader.read_event().map_err(|err| xml_error(part, err))? {
+            Event::Eof => return Ok(()),
+            event => handle(event)?,
+        }
+    }
+}
+
+// an attribute by its name without any namespace prefix, so r:id is just "id".
+fn attribute(element: &BytesStart, name: &[u8], part: &str) -> Result<Option<String>, XlsxError> {
+    for attr in element.attributes() {
+        let attr = attr.map_err(|err| xml_error(part, err))?;
+        if attr.key.local_name().as_ref() == name {
+            return Ok(Some(attr.unescape_value().map_err(|err| xml_error(part, err))?.into_owned()));
+        }
+    }
+    Ok(None)
+}
+
+struct Relationship {
+    id: String,
+    kind: String,
+    target: String, // the path of the part inside the zip.
+}
+
+// targets are relative to the folder of the part the relationships belong to, `base`.
+fn relationships(xml: &str, part: &str, base: &str) -> Result<Vec<Relationship>, XlsxError> {
+    let mut rels = Vec::new();
+    each_event(xml, part, |event| {
+        if let Event::Start(element) | Event::Empty(element) = event {
+            if element.local_name().as_ref() == b"Relationship" {
+                let target = attribute(&element, b"Target", part)?.unwrap_or_default();
+                let target = match target.strip_prefix('/') {
+                    Some(absolute) => absolute.to_string(),
+                    None => format!("{}{}", base, target),
+                };
+                rels.push(Relationship {
+                    id: attribute(&element, b"Id", part)?.unwrap_or_default(),
+                    kind: attribute(&element, b"Type", part)?.unwrap_or_default(),
+                    target,
+                });
+            }
+        }
+        Ok(())
+    })?;
+    Ok(rels)
+}
+
+// each sheet's name and the id of the relationship that says where it is.
+fn sheet_list(xml: &str, part: &str) -> Result<Vec<(String, String)>, XlsxError> {
+    let mut sheets = Vec::new();
+    each_event(xml, part, |event| {
+        if let Event::Start(element) | Event::Empty(element) = event {
+            if element.local_name().as_ref() == b"sheet" {
+                let name = attribute(&element, b"name", part)?.unwrap_or_default();
+                let id = attribute(&element, b"id", part)?.unwrap_or_default();
+                sheets.push((name, id));
+            }
+        }
+        Ok(())
+    })?;
+    Ok(sheets)
+}
+
+// every string the sheets point at by index. rich text is made of runs that are joined back up,
+// the phonetic guides some east asian text has are left out.
+fn shared_strings(xml: &str, part: &str, pool: &mut Interner) -> Result<Vec<Arc<str>>, XlsxError> {
+    let mut strings = Vec::new();
+    let mut text = String::new();
+    let (mut in_text, mut in_phonetic) = (false, false);
+    each_event(xml, part, |event| {
+        match event {
+            Event::Start(element) => match element.local_name().as_ref() {
+                b"si" => text.clear(),
+                b"t" => in_text = !in_phonetic,
+                b"rPh" => in_phonetic = true,
+                _ => {}
+            },
+            Event::Empty(element) if element.local_name().as_ref() == b"si" => strings.push(pool.intern_shared("")),
+            Event::End(element) => match element.local_name().as_ref() {
+                b"si" => strings.push(pool.intern_shared(&text)),
+                b"t" => in_text = false,
+                b"rPh" => in_phonetic = false,
+                _ => {}
+            },
+            Event::Text(content) if in_text => text.push_str(&content.unescape().map_err(|err| xml_error(part, err))?),
+            Event::CData(content) if in_text => text.push_str(&String::from_utf8_lossy(&content)),
+            _ => {}
+        }
+        Ok(())
+    })?;
+    Ok(strings)
+}
+
+// for each cell style, by its index, whether numbers in it are dates. excel stores a date as the
+// number of days since 1900, it's only the style that makes it one.
+fn date_styles(xml: &str, part: &str) -> Result<Vec<bool>, XlsxError> {
+    let mut custom = HashMap::new();
+    let mut styles = Vec::new();
+    let mut in_cell_styles = false;
+    each_event(xml, part, |event| {
+        match event {
+            Event::Start(element) | Event::Empty(element) => match element.local_name().as_ref() {
+                b"numFmt" => {
+                    let id = attribute(&element, b"numFmtId", part)?.and_then(|id| id.parse::<u32>().ok());
+                    let code = attribute(&element, b"formatCode", part)?.unwrap_or_default();
+                    if let Some(id) = id {
+                        custom.insert(id, is_date_format(&code));
+                    }
+                }
+                b"cellXfs" => in_cell_styles = true,
+                b"xf" if in_cell_styles => {
+                    let id = attribute(&element, b"numFmtId", part)?.and_then(|id| id.parse::<u32>().ok());
+                    styles.push(id.unwrap_or(0));
+                }
+                _ => {}
+            },
+            Event::End(element) if element.local_name().as_ref() == b"cellXfs" => in_cell_styles = false,
+            _ => {}
+        }
+        Ok(())
+    })?;
+
+    // the built in formats 14 to 22 are dates, 18 to 21 are times of day though.
+    Ok(styles
+        .into_iter()
+        .map(|id| custom.get(&id).copied().unwrap_or(matches!(id, 14..=17 | 22)))
+        .collect())
+}
+
+// a format like "yyyy\-mm\-dd" or "d mmm", anything in quotes, brackets or escaped is left out
+// since it's shown as it is.
+fn is_date_format(code: &str) -> bool {
+    let mut chars = code.chars();
+    let mut date = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => for c in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+            },
+            '[' => for c in chars.by_ref() {
+                if c == ']' {
+                    break;
+                }
+            },
+            '\\' | '_' | '*' => {
+                chars.next();
+            }
+            'd' | 'D' | 'y' | 'Y' => date = true,
+            _ => {}
+        }
+    }
+    date
+}
+
+// day 1 is 1900-01-01, and excel thinks 1900 was a leap year so day 60 is a 29th of february that
+// never happened.
+fn serial_to_date(serial: i64) -> Option<Date> {
+    match serial {
+        1..=59 => Date::from_days(serial - 25568),
+        61.. => Date::from_days(serial - 25569),
+        _ => None,
+    }
+}
+
+// moves the relative references in a formula, the ones without a $, by the given rows and columns.
+// a shared formula is only written out once for a whole block of cells, each cell after the first
+// gets the first one's formula moved over to where it is.
+fn shift_formula(formula: &str, rows: i64, cols: i64) -> String {
+    let chars: Vec<char> = formula.chars().collect();
+    let mut out = String::with_capacity(formula.len());
+    let mut idx = 0;
+    while idx < chars.len() {
+        let c = chars[idx];
+        if c == '"' || c == '\'' {
+            // strings and quoted sheet names are copied over as they are.
+            let end = quoted_end(&chars, idx);
+            out.extend(&chars[idx..end]);
+            idx = end;
+            continue;
+        }
+
+        // a sheet name like Q1 in Q1!A1 looks like a reference too, but it's followed by the '!'.
+        let starts_word = idx == 0 || !(chars[idx - 1].is_ascii_alphanumeric() || matches!(chars[idx - 1], '_' | '.'));
+        let reference = reference_at(&chars[idx..]).filter(|(len, ..)| starts_word && chars.get(idx + len) != Some(&'!'));
+        match reference {
+            Some((len, col_fixed, letters, row_fixed, digits)) => {
+                let col = CellAddress::parse_column(&letters).unwrap_or(0) as i64;
+                let row = digits.parse::<i64>().unwrap_or(1);
+                let col = if col_fixed { col } else { col + cols };
+                let row = if row_fixed { row } else { row + rows };
+                if col < 0 || row < 1 {
+                    out.push_str("#REF!");
+                } else {
+                    out.push_str(if col_fixed { "$" } else { "" });
+                    out.push_str(&CellAddress::column_name(col as usize));
+                    out.push_str(if row_fixed { "$" } else { "" });
+                    out.push_str(&row.to_string());
+                }
+                idx += len;
+            }
+            None => {
+                out.push(c);
+                idx += 1;
+            }
+        }
+    }
+    out
+}
+
+// just past the quote that closes the string or sheet name opened at `start`. a doubled quote is a
+// quote inside of it, not the end.
+fn quoted_end(chars: &[char], start: usize) -> usize {
+    let quote = chars[start];
+    let mut idx = start + 1;
+    while idx < chars.len() {
+        if chars[idx] == quote {
+            if chars.get(idx + 1) != Some(&quote) {
+                return idx + 1;
+            }
+            idx += 1;
+        }
+        idx += 1;
+    }
+    chars.len()
+}
+
+// a reference like A1, $B$2 or AB$10 at the start of `chars` that isn't part of a longer word or a
+// function name: (its length, whether the column is fixed, the column letters, whether the row is
+// fixed, the row digits).
+fn reference_at(chars: &[char]) -> Option<(usize, bool, String, bool, String)> {
+    let mut idx = 0;
+    let col_fixed = chars.first() == Some(&'$');
+    idx += col_fixed as usize;
+    let letters: String = chars[idx..].iter().take_while(|c| c.is_ascii_uppercase()).collect();
+    idx += letters.len();
+    let row_fixed = chars.get(idx) == Some(&'$');
+    idx += row_fixed as usize;
+    let digits: String = chars[idx..].iter().take_while(|c| c.is_ascii_digit()).collect();
+    idx += digits.len();
+
+    let next = chars.get(idx);
+    let ends_word = next.is_none_or(|c| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '(' | '.')));
+    (!letters.is_empty() && letters.len() <= 3 && !digits.is_empty() && ends_word)
+        .then_some((idx, col_fixed, letters, row_fixed, digits))
+}
+
+// the cell being read, its value comes in pieces between its start and end tags.
+struct CellState {
+    address: CellAddress,
+    kind: String, // the t attribute, "s" for a shared string, "b" for a bool and so on.
+    style: usize,
+    value: Option<String>,
+    formula: Option<String>,
+    shared_formula: Option<String>, // the si of a formula shared with other cells.
+    inline: Option<String>, // the text of an inlineStr.
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Target {
+    None,
+    Value,
+    Formula,
+    Inline,
+}
+
+struct SheetReader<'a> {
+    shared_strings: &'a [Arc<str>],
+    date_styles: &'a [bool],
+    pool: &'a mut Interner,
+    masters: HashMap<String, (String, CellAddress)>, // shared formulas by si, with the cell they're written in.
+}
+
+impl SheetReader<'_> {
+    // cells can be left out, and so can rows, the gaps are blank. cells without an r attribute
+    // follow on from the one before.
+    fn read(&mut self, xml: &str, part: &str) -> Result<Vec<Vec<Field>>, XlsxError> {
+        let mut rows: Vec<Vec<Field>> = Vec::new();
+        let mut cell: Option<CellState> = None;
+        let mut target = Target::None;
+        let (mut row, mut col) = (0, 0);
+        let mut in_phonetic = false;
+        let mut first_row = true;
+
+        each_event(xml, part, |event| {
+            match event {
+                Event::Start(ref element) | Event::Empty(ref element) => {
+                    let empty = matches!(event, Event::Empty(_));
+                    match element.local_name().as_ref() {
+                        b"row" => {
+                            let number = attribute(element, b"r", part)?.and_then(|r| r.parse::<usize>().ok());
+                            row = match number {
+                                Some(number) => number.saturating_sub(1),
+                                None if first_row => 0,
+                                None => row + 1,
+                            };
+                            if row >= CellAddress::MAX_ROWS {
+                                return Err(out_of_range(part, format!("row {}", row + 1)));
+                            }
+                            first_row = false;
+                            col = 0;
+                        }
+                        b"c" => {
+                            let address = attribute(element, b"r", part)?
+                                .and_then(|r| CellAddress::parse(&r))
+                                .unwrap_or(CellAddress { row, col });
+                            col = address.col + 1;
+                            let state = CellState {
+                                address,
+                                kind: attribute(element, b"t", part)?.unwrap_or_default(),
+                                style: attribute(element, b"s", part)?.and_then(|s| s.parse().ok()).unwrap_or(0),
+                                value: None,
+                                formula: None,
+                                shared_formula: None,
+                                inline: None,
+                            };
+                            if empty {
+                                self.place(&mut rows, state, part)?;
+                            } else {
+                                cell = Some(state);
+                            }
+                        }
+                        b"v" if !empty => target = Target::Value,
+                        b"f" => {
+                            if let Some(cell) = cell.as_mut() {
+                                if attribute(element, b"t", part)?.as_deref() == Some("shared") {
+                                    cell.shared_formula = attribute(element, b"si", part)?;
+                                }
+                                if !empty {
+                                    cell.formula = Some(String::new());
+                                    target = Target::Formula;
+                                }
+                            }
+                        }
+                        b"t" if !empty && !in_phonetic => target = Target::Inline,
+                        b"rPh" if !empty => in_phonetic = true,
+                        _ => {}
+                    }
+                }
+
+                Event::Text(content) if target != Target::None => {
+                    let content = content.unescape().map_err(|err| xml_error(part, err))?;
+                    if let Some(cell) = cell.as_mut() {
+                        let text = match target {
+                            Target::Value => cell.value.get_or_insert_with(String::new),
+                            Target::Formula => cell.formula.get_or_insert_with(String::new),
+                            _ => cell.inline.get_or_insert_with(String::new),
+                        };
+                        text.push_str(&content);
+                    }
+                }
+
+                Event::End(element) => match element.local_name().as_ref() {
+                    b"c" => {
+                        if let Some(state) = cell.take() {
+                            self.place(&mut rows, state, part)?;
+                        }
+                    }
+                    b"v" | b"f" | b"t" => target = Target::None,
+                    b"rPh" => in_phonetic = false,
+                    _ => {}
+                },
+
+                _ => {}
+            }
+            Ok(())
+        })?;
+        Ok(rows)
+    }
+
+    // the grid only grows as far as the cells in it, and never past the end of a sheet.
+    fn place(&mut self, rows: &mut Vec<Vec<Field>>, cell: CellState, part: &str) -> Result<(), XlsxError> {
+        let address = cell.address;
+        if !address.in_sheet() {
+            let reference = format!("{}{}", CellAddress::column_name(address.col), address.row + 1);
+            return Err(out_of_range(part, reference));
+        }
+        let value = self.value(cell, part)?;
+        if rows.len() <= address.row {
+            rows.resize_with(address.row + 1, Vec::new);
+        }
+        let row = &mut rows[address.row];
+        if row.len() <= address.col {
+            row.resize_with(address.col + 1, || Field::new(FieldValue::Empty));
+        }
+        row[address.col] = Field::new(value);
+        Ok(())
+    }
+
+    fn value(&mut self, cell: CellState, part: &str) -> Result<FieldValue, XlsxError> {
+        let formula = match (cell.formula, cell.shared_formula) {
+            // the first cell of a shared formula has the text, the rest only the si.
+            (Some(formula), Some(si)) if !formula.is_empty() => {
+                self.masters.insert(si, (formula.clone(), cell.address));
+                Some(formula)
+            }
+            (_, Some(si)) => self.masters.get(&si).map(|(formula, master)| {
+                let rows = cell.address.row as i64 - master.row as i64;
+                let cols = cell.address.col as i64 - master.col as i64;
+                shift_formula(formula, rows, cols)
+            }),
+            (formula, None) => formula.filter(|formula| !formula.is_empty()),
+        };
+        if let Some(formula) = formula {
+            return Ok(FieldValue::Formula(format!("={}", formula)));
+        }
+
+        if cell.kind == "inlineStr" {
+            let text = cell.inline.unwrap_or_default();
+            return Ok(FieldValue::String(Text::Pooled(self.pool.intern_shared(&text))));
+        }
+        let value = match cell.value {
+            Some(value) => value,
+            None => return Ok(FieldValue::Empty),
+        };
+        Ok(match cell.kind.as_str() {
+            "s" => {
+                let text = value
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|idx| self.shared_strings.get(idx))
+                    .ok_or_else(|| xml_error(part, format!("no shared string {} for {}", value, cell.address)))?;
+                FieldValue::String(Text::Pooled(Arc::clone(text)))
+            }
+            "b" => FieldValue::Bool(value.trim() == "1" || value.trim().eq_ignore_ascii_case("true")),
+            "d" => match value.get(..10).and_then(|day| Date::parse(day, "%Y-%m-%d")) {
+                Some(date) => FieldValue::Date(date),
+                None => FieldValue::String(Text::Pooled(self.pool.intern_shared(&value))),
+            },
+            "str" | "e" => FieldValue::String(Text::Pooled(self.pool.intern_shared(&value))),
+            _ => match Value::parse(value.trim(), false) {
+                // only whole days are turned into dates, a date with a time is left as a number.
+                Value::Integer(serial) if self.date_styles.get(cell.style).copied().unwrap_or(false) => {
+                    serial_to_date(serial).map_or(FieldValue::Integer(serial), FieldValue::Date)
+                }
+                Value::Integer(int) => FieldValue::Integer(int),
+                Value::Float(float) => FieldValue::Float(float),
+                _ => FieldValue::String(Text::Pooled(self.pool.intern_shared(&value))),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use crate::eval::{Computed, Evaluator};
+    use crate::excel::Grid;
+    use crate::workbook::Workbook;
+
+    fn cell(excel: &Excel, reference: &str) -> String {
+        let value = excel.value(CellAddress::parse(reference).unwrap()).unwrap();
+        format!("{:?}", value)
+    }
+
+    #[test]
+    fn test_read_sheets() {
+        let sheets = read_sheets(File::open("test_data/workbook.xlsx").unwrap()).unwrap();
+        let names: Vec<&str> = sheets.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["Data", "Summary"]);
+
+        let data = &sheets[0].1;
+        assert_eq!(data.rows.len(), 6);
+        assert_eq!(cell(data, "A1"), r#"String("name")"#);
+        assert_eq!(cell(data, "B2"), "Integer(3)");
+        assert_eq!(cell(data, "C2"), "Float(0.5)");
+        assert_eq!(cell(data, "C4"), "Float(10.0)");
+        assert_eq!(cell(data, "E2"), "Date(Date { year: 2023, month: 10, day: 19 })");
+        assert_eq!(cell(data, "E3"), "Bool(true)");
+        assert_eq!(cell(data, "E4"), r##"String("#N/A")"##);
+        // rich text runs are joined, entities unescaped and the phonetic guide left out.
+        assert_eq!(cell(data, "A3"), r#"String("pear & plum")"#);
+        assert_eq!(cell(data, "A4"), r#"String("kiwi")"#);
+        assert_eq!(cell(data, "A6"), r#"String(" x<y ")"#);
+
+        // gaps in a row, and whole rows that were left out, are blank.
+        assert_eq!(cell(data, "B4"), "Empty");
+        assert!(data.rows[4].is_empty());
+
+        // shared formulas are moved to the cell they're in.
+        assert_eq!(cell(data, "D2"), r#"Formula("=B2*C2")"#);
+        assert_eq!(cell(data, "D3"), r#"Formula("=B3*C3")"#);
+        assert_eq!(cell(data, "D4"), r#"Formula("=B4*C4")"#);
+        assert_eq!(cell(data, "F6"), r#"Formula("=Summary!B1*$B$2")"#);
+
+        let summary = &sheets[1].1;
+        assert_eq!(cell(summary, "C1"), "Date(Date { year: 2023, month: 10, day: 19 })");
+        assert_eq!(cell(summary, "B2"), r#"Formula("=Data!A2")"#);
+    }
+
+    #[test]
+    fn test_prefixed_parts() {
+        let sheets = read_sheets(File::open("test_data/prefixed.xlsx").unwrap()).unwrap();
+        assert_eq!(sheets.len(), 1);
+        let (name, excel) = &sheets[0];
+        assert_eq!(name, "Only");
+        let cells: Vec<String> = ["A1", "B1", "C1", "D1", "A2", "B2"].iter().map(|r| cell(excel, r)).collect();
+        assert_eq!(
+            cells,
+            [
+                r#"String("a")"#,
+                "Float(1500.0)",
+                "Empty",
+                "Bool(false)",
+                r#"Formula("=B1/3")"#,
+                "Date(Date { year: 2023, month: 10, day: 19 })"
+            ]
+        );
+
+        assert!(matches!(read_sheets(File::open("test_data/formulas.csv").unwrap()), Err(XlsxError::Zip(_))));
+    }
+
+    #[test]
+    fn test_formulas_work_out() {
+        let workbook = Workbook::from_xlsx("test_data/workbook.xlsx").unwrap();
+        let mut evaluator = Evaluator::for_workbook(&workbook);
+        let value = |evaluator: &mut Evaluator, sheet, reference| evaluator.cell(sheet, CellAddress::parse(reference).unwrap());
+        assert_eq!(value(&mut evaluator, 0, "D6"), Computed::Number(2.5));
+        assert_eq!(value(&mut evaluator, 0, "F6"), Computed::Number(30.0));
+        assert_eq!(value(&mut evaluator, 1, "A2"), Computed::Number(2.5));
+        assert_eq!(value(&mut evaluator, 1, "B2"), Computed::Text("apple".to_string()));
+
+        let excel = Excel::from_xlsx("test_data/workbook.xlsx", Some("summary")).unwrap();
+        assert_eq!(cell(&excel, "B1"), "Integer(10)");
+        assert!(matches!(Excel::from_xlsx("test_data/workbook.xlsx", Some("Nope")), Err(XlsxError::UnknownSheet(_))));
+    }
+
+    #[test]
+    fn test_cells_past_the_end_of_a_sheet() {
+        let read = |xml: &str| {
+            let mut pool = Interner::new();
+            let mut reader = SheetReader {
+                shared_strings: &[],
+                date_styles: &[],
+                pool: &mut pool,
+                masters: HashMap::new(),
+            };
+            reader.read(xml, "xl/worksheets/sheet1.xml")
+        };
+        let sheet = |rows: &str| format!("<worksheet><sheetData>{}</sheetData></worksheet>", rows);
+
+        let rows = read(&sheet(r#"<row r="1048576"><c r="XFD1048576"><v>1</v></c></row>"#)).unwrap();
+        assert_eq!(rows.len(), CellAddress::MAX_ROWS);
+        assert_eq!(rows[CellAddress::MAX_ROWS - 1].len(), CellAddress::MAX_COLUMNS);
+
+        for rows in [
+            r#"<row r="1"><c r="A18446744073709551615"><v>1</v></c></row>"#,
+            r#"<row r="1"><c r="A100000000"><v>1</v></c></row>"#,
+            r#"<row r="1"><c r="XFE1"><v>1</v></c></row>"#,
+            r#"<row r="1048577"><c><v>1</v></c></row>"#,
+        ] {
+            let err = read(&sheet(rows)).unwrap_err();
+            assert!(matches!(err, XlsxError::OutOfRange { .. }), "{}: {:?}", rows, err);
+        }
+        let err = read(&sheet(r#"<row r="1"><c r="A100000000"/></row>"#)).unwrap_err();
+        assert_eq!(err.to_string(), "A100000000 in xl/worksheets/sheet1.xml is past the end of a sheet, XFD1048576");
+    }
+
+    #[test]
+    fn test_part_size_limit() {
+        assert_eq!(read_limited(&b"<a/>"[..], "xl/workbook.xml", 4).unwrap(), "<a/>");
+        let err = read_limited(&b"<ab/>"[..], "xl/workbook.xml", 4).unwrap_err();
+        assert!(matches!(&err, XlsxError::TooLarge(part) if part == "xl/workbook.xml"), "{:?}", err);
+    }
+
+    #[test]
+    fn test_shift_formula() {
+        assert_eq!(shift_formula("B2*C2", 1, 0), "B3*C3");
+        assert_eq!(shift_formula("SUM($A1:A$1)+Sheet2!B2", 2, 1), "SUM($A3:B$1)+Sheet2!C4");
+        assert_eq!(shift_formula("LOG10(A1)&\"A1\"", 1, 1), "LOG10(B2)&\"A1\"");
+        assert_eq!(shift_formula("A1", -1, 0), "#REF!");
+        // nothing inside of strings or quoted sheet names moves, however they're escaped.
+        assert_eq!(shift_formula("\"say \"\"A1\"\"\"&A1", 1, 0), "\"say \"\"A1\"\"\"&A2");
+        assert_eq!(shift_formula("'Sheet A1'!B2+'Bob''s A1'!C3", 1, 0), "'Sheet A1'!B3+'Bob''s A1'!C4");
+        assert_eq!(shift_formula("Q1!A1+\"open A1", 1, 0), "Q1!A2+\"open A1");
+        assert_eq!(serial_to_date(1), Date::new(1900, 1, 1));
+        assert_eq!(serial_to_date(60), None);
+        assert_eq!(serial_to_date(61), Date::new(1900, 3, 1));
+        assert!(is_date_format("yyyy\\-mm\\-dd"));
+        assert!(!is_date_format("0.00\"d\""));
+        assert!(!is_date_format("[Red]0.00"));
+    }
+}